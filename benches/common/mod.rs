@@ -0,0 +1,123 @@
+//! Synthetic documents shared by the benchmarks.
+//!
+//! Every generator is deterministic so numbers can be compared between runs.
+#![allow(dead_code)]
+
+use serde_json::{json, Map, Value};
+
+/// Builds an object with `width` scalar keys. Every `step`-th key holds a
+/// different value when `version` is not zero, `step == 0` changes nothing.
+pub fn wide(width: usize, step: usize, version: usize) -> Value {
+    let mut map = Map::new();
+    for index in 0..width {
+        let changed = version > 0 && step > 0 && index % step == 0;
+        map.insert(
+            format!("key_{}", index),
+            if changed {
+                json!(format!("changed {}", index))
+            } else {
+                json!(index)
+            },
+        );
+    }
+    Value::Object(map)
+}
+
+/// Builds objects nested `depth` levels deep with a few siblings per level.
+/// The innermost leaf differs between versions.
+pub fn deep(depth: usize, version: usize) -> Value {
+    let mut value = json!({"leaf": version});
+    for level in (0..depth).rev() {
+        value = json!({
+            "level": level,
+            "name": format!("level {}", level),
+            "child": value,
+        });
+    }
+    value
+}
+
+/// Builds `{"items": [...]}` with `length` items, with `prepended` new items
+/// inserted at the front of the array.
+pub fn array(length: usize, prepended: usize) -> Value {
+    let items: Vec<Value> = (0..prepended)
+        .map(|index| json!(format!("new_{}", index)))
+        .chain((0..length).map(|index| json!(format!("item_{}", index))))
+        .collect();
+    json!({ "items": items })
+}
+
+/// Builds an object with `records` entries, each one a small nested record.
+/// Every `step`-th record is modified when `version` is not zero.
+pub fn records(records: usize, step: usize, version: usize) -> Value {
+    let mut map = Map::new();
+    for index in 0..records {
+        let changed = version > 0 && step > 0 && index % step == 0;
+        map.insert(
+            format!("record_{}", index),
+            json!({
+                "id": index,
+                "name": format!("name {}", index),
+                "active": index % 2 == 0,
+                "score": if changed { index as f64 * 1.5 } else { index as f64 },
+                "tags": ["alpha", "beta", if changed { "delta" } else { "gamma" }],
+                "address": {
+                    "street": format!("{} main street", index),
+                    "city": "Dhaka",
+                    "location": {"lat": 23.8, "lng": 90.4},
+                },
+            }),
+        );
+    }
+    Value::Object(map)
+}
+
+/// Returns the serialized size of a value, used for throughput.
+pub fn size(value: &Value) -> u64 {
+    serde_json::to_string(value).unwrap().len() as u64
+}
+
+/// The pairs of documents every benchmark runs against.
+pub fn cases() -> Vec<(String, Value, Value)> {
+    let mut cases = Vec::new();
+
+    for width in [1_000, 10_000, 100_000] {
+        cases.push((
+            format!("wide_{}_small_diff", width),
+            wide(width, 0, 0),
+            wide(width, width, 1),
+        ));
+        cases.push((
+            format!("wide_{}_large_diff", width),
+            wide(width, 0, 0),
+            wide(width, 2, 1),
+        ));
+    }
+
+    for depth in [16, 64, 256] {
+        cases.push((format!("deep_{}", depth), deep(depth, 0), deep(depth, 1)));
+    }
+
+    for length in [1_000, 10_000] {
+        cases.push((
+            format!("array_{}_append", length),
+            array(length, 0),
+            array(length + 10, 0),
+        ));
+        cases.push((
+            format!("array_{}_prepend", length),
+            array(length, 0),
+            array(length, 10),
+        ));
+    }
+
+    for count in [1_000, 10_000, 50_000] {
+        cases.push((
+            format!("records_{}", count),
+            records(count, 0, 0),
+            records(count, 100, 1),
+        ));
+    }
+
+    cases
+}
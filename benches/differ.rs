@@ -0,0 +1,44 @@
+mod common;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use json_differ::differ::Differ;
+
+fn bench_diff(c: &mut Criterion) {
+    let mut group = c.benchmark_group("diff");
+    group.sample_size(10);
+
+    for (name, left, right) in common::cases() {
+        group.throughput(Throughput::Bytes(common::size(&left)));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(name),
+            &(left, right),
+            |b, (left, right)| {
+                b.iter(|| {
+                    let mut differ = Differ::new_from_json_values(left.clone(), right.clone());
+                    differ.diff().get_deltas().len()
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+fn bench_is_equal(c: &mut Criterion) {
+    let mut group = c.benchmark_group("is_equal");
+    group.sample_size(10);
+
+    for (name, left, _) in common::cases() {
+        let differ = Differ::new_from_json_values(left.clone(), left.clone());
+
+        group.throughput(Throughput::Bytes(common::size(&left)));
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter(|| differ.is_equal())
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_diff, bench_is_equal);
+criterion_main!(benches);
@@ -0,0 +1,41 @@
+mod common;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use json_differ::delta::{Delta, Operation};
+use serde_json::{json, Value};
+
+fn bench_delta_hash(c: &mut Criterion) {
+    let mut group = c.benchmark_group("delta_hash");
+
+    let values = vec![
+        ("scalar", json!(1), json!(2)),
+        ("string", json!("a".repeat(1_000)), json!("b".repeat(1_000))),
+        ("records_100", Value::Null, common::records(100, 0, 0)),
+        ("records_1000", Value::Null, common::records(1_000, 0, 0)),
+    ];
+
+    for (name, old_value, new_value) in values {
+        group.throughput(Throughput::Bytes(
+            common::size(&old_value) + common::size(&new_value),
+        ));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(name),
+            &(old_value, new_value),
+            |b, (old_value, new_value)| {
+                b.iter(|| {
+                    Delta::new(
+                        Operation::Change,
+                        "$.records".to_string(),
+                        old_value.clone(),
+                        new_value.clone(),
+                    )
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_delta_hash);
+criterion_main!(benches);
@@ -0,0 +1,27 @@
+mod common;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use json_differ::differ::Differ;
+use json_differ::patcher::patch;
+
+fn bench_patch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("patch");
+    group.sample_size(10);
+
+    for (name, left, right) in common::cases() {
+        let mut differ = Differ::new_from_json_values(left.clone(), right);
+        let deltas = differ.diff().get_deltas().clone();
+
+        group.throughput(Throughput::Elements(deltas.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(name),
+            &(left, deltas),
+            |b, (left, deltas)| b.iter(|| patch(left.clone(), deltas, Default::default())),
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_patch);
+criterion_main!(benches);
@@ -0,0 +1,39 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use json_differ::delta::{Delta, Operation};
+use json_differ::patcher::{try_patch, PatchOptions};
+use libfuzzer_sys::fuzz_target;
+use serde_json::Value;
+
+#[derive(Debug, Arbitrary)]
+struct Input<'a> {
+    base: &'a str,
+    deltas: Vec<(u8, &'a str, &'a str)>,
+    omit_empty: bool,
+}
+
+/// Parses the text as JSON, or keeps it as a string
+fn value(text: &str) -> Value {
+    serde_json::from_str(text).unwrap_or_else(|_| Value::from(text))
+}
+
+// Applies arbitrary lists of deltas to arbitrary documents, as deltas received
+// over the network would be.
+fuzz_target!(|input: Input| {
+    let deltas: Vec<Delta> = input
+        .deltas
+        .iter()
+        .map(|(operation, path, new_value)| {
+            let operation = match operation % 3 {
+                0 => Operation::Add,
+                1 => Operation::Change,
+                _ => Operation::Delete,
+            };
+            Delta::new(operation, path.to_string(), Value::Null, value(new_value))
+        })
+        .collect();
+
+    let options = PatchOptions::default().omit_empty(input.omit_empty);
+    let _ = try_patch(value(input.base), &deltas, options);
+});
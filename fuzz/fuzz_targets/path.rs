@@ -0,0 +1,22 @@
+#![no_main]
+
+use json_differ::delta::{compare_paths, Delta, Operation};
+use json_differ::patcher::{try_patch, PatchOptions};
+use libfuzzer_sys::fuzz_target;
+use serde_json::{json, Value};
+
+// Feeds arbitrary paths to the path parser, through `compare_paths` and every
+// operation of the patcher on a document holding objects and nested arrays.
+fuzz_target!(|input: (&str, &str)| {
+    let (path, other) = input;
+    let _ = compare_paths(path, other);
+
+    let base = json!({"a": [1, [2, {"b": 3}], []], "c": {"d": {}}});
+    for operation in [Operation::Add, Operation::Change, Operation::Delete] {
+        let delta = Delta::new(operation, path.to_string(), Value::Null, json!({"e": [4]}));
+        for omit_empty in [false, true] {
+            let options = PatchOptions::default().omit_empty(omit_empty);
+            let _ = try_patch(base.clone(), std::slice::from_ref(&delta), options);
+        }
+    }
+});
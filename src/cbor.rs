@@ -0,0 +1,44 @@
+//! CBOR documents, behind the `cbor` feature.
+//!
+//! Documents are decoded to `Value`, so byte strings and maps with keys that
+//! are not strings are rejected. Delta lists can be stored in the compact form
+//! of `delta::compact`.
+
+use crate::delta::compact::{Compact, CompactRef};
+use crate::delta::Delta;
+use crate::errors::ProcessError;
+use serde_json::Value;
+
+/// Decodes a CBOR document into a `Value`
+pub fn from_slice(bytes: &[u8]) -> Result<Value, ProcessError> {
+    ciborium::from_reader(bytes).map_err(|error| invalid(error.to_string()))
+}
+
+/// Encodes a `Value` as a CBOR document
+pub fn to_vec(value: &Value) -> Result<Vec<u8>, ProcessError> {
+    let mut bytes = Vec::new();
+    ciborium::into_writer(value, &mut bytes).map_err(|error| invalid(error.to_string()))?;
+    Ok(bytes)
+}
+
+/// Encodes deltas in their compact form
+pub fn deltas_to_vec(deltas: &[Delta]) -> Result<Vec<u8>, ProcessError> {
+    let mut bytes = Vec::new();
+    ciborium::into_writer(&CompactRef(deltas), &mut bytes)
+        .map_err(|error| invalid(error.to_string()))?;
+    Ok(bytes)
+}
+
+/// Decodes deltas encoded with `deltas_to_vec`
+pub fn deltas_from_slice(bytes: &[u8]) -> Result<Vec<Delta>, ProcessError> {
+    let Compact(deltas) =
+        ciborium::from_reader(bytes).map_err(|error| invalid(error.to_string()))?;
+    Ok(deltas)
+}
+
+fn invalid(message: String) -> ProcessError {
+    ProcessError::InvalidDocument {
+        format: "CBOR".to_string(),
+        message,
+    }
+}
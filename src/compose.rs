@@ -0,0 +1,185 @@
+//! Composition of delta lists, to squash a sequence of patches into one.
+
+use crate::delta::{compare_paths, invert, Delta, Operation};
+use crate::patcher::{patch, PatchOptions};
+use crate::utils::{is_path_prefix, path_prefixes, segments, Segment};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+
+/// Which of the two lists a delta comes from
+#[derive(Clone, Copy, PartialEq)]
+enum Side {
+    First,
+    Second,
+}
+
+/// Returns a single delta list equivalent to applying `first` and then
+/// `second`.
+///
+/// Deltas on unrelated paths are kept as they are. Deltas on the same path,
+/// or on nested paths, are folded into one delta on the shortest path: an
+/// addition followed by a deletion cancels out, two changes become one change
+/// from the original `old_value`, and a change back to the original value
+/// disappears.
+///
+/// The result is exact for delta lists produced by `Differ`, where arrays only
+/// grow or shrink at their end, so an index refers to the same element before
+/// and after each list is applied. The deltas are sorted by path.
+pub fn compose(first: &[Delta], second: &[Delta]) -> Vec<Delta> {
+    let clashes = clashes(first, second);
+
+    // every delta is grouped under its path, or under the level that changed
+    // kind between the two lists
+    let mut deltas: Vec<(&str, Side, &Delta)> = first
+        .iter()
+        .map(|delta| (Side::First, delta))
+        .chain(second.iter().map(|delta| (Side::Second, delta)))
+        .map(|(side, delta)| {
+            let key = path_prefixes(&delta.path)
+                .find(|prefix| clashes.contains(prefix))
+                .unwrap_or(&delta.path);
+            (key, side, delta)
+        })
+        .collect();
+
+    // a stable sort keeps the first list before the second on equal paths
+    deltas.sort_by(|(a, _, _), (b, _, _)| compare_paths(a, b));
+
+    let mut composed = Vec::new();
+    let mut start = 0;
+    for end in 1..=deltas.len() {
+        let (root, _, _) = deltas[start];
+        if end < deltas.len() && is_path_prefix(root, deltas[end].0) {
+            continue;
+        }
+        composed.extend(fold(root, &deltas[start..end]));
+        start = end;
+    }
+
+    composed
+}
+
+/// Returns the paths where the deltas of one list go on with an array index
+/// and the deltas of the other list with an object key, or the other way
+/// round. This happens when the first list removes an object or array, one
+/// entry at a time, and the second one adds a value of the other kind.
+pub(crate) fn clashes<'a>(first: &'a [Delta], second: &'a [Delta]) -> HashSet<&'a str> {
+    let children = |deltas: &'a [Delta]| {
+        let mut children: HashMap<&str, (bool, bool)> = HashMap::new();
+        for delta in deltas {
+            for prefix in path_prefixes(&delta.path) {
+                let (index, key) = children.entry(prefix).or_default();
+                match segments(&delta.path[prefix.len()..]).next() {
+                    Some(Segment::Index(_)) => *index = true,
+                    Some(Segment::Key(_)) => *key = true,
+                    None => {}
+                }
+            }
+        }
+        children
+    };
+
+    let first_children = children(first);
+    let second_children = children(second);
+
+    first_children
+        .iter()
+        .filter(
+            |(prefix, (index, key))| match second_children.get(*prefix) {
+                Some((other_index, other_key)) => (*index && *other_key) || (*key && *other_index),
+                None => false,
+            },
+        )
+        .map(|(prefix, _)| *prefix)
+        .collect()
+}
+
+/// Folds a group of deltas, all nested in `path`, into one delta on `path`
+fn fold(path: &str, group: &[(&str, Side, &Delta)]) -> Vec<Delta> {
+    if let [(_, _, delta)] = group {
+        return vec![(*delta).clone()];
+    }
+
+    let at = |wanted: Side| {
+        group
+            .iter()
+            .find(|(_, side, delta)| *side == wanted && delta.path == path)
+            .map(|(_, _, delta)| *delta)
+    };
+    let nested = |wanted: Side| -> Vec<Delta> {
+        group
+            .iter()
+            .filter(|(_, side, delta)| *side == wanted && delta.path != path)
+            .map(|(_, _, delta)| relative(delta, path))
+            .collect()
+    };
+    let only = |deltas: &[Delta], operation: Operation| {
+        deltas.iter().all(|delta| delta.operation == operation)
+    };
+
+    let (original, composed) = match (at(Side::First), at(Side::Second)) {
+        // the first list replaced the whole value
+        (Some(first), second) => {
+            let (original, intermediate) = values(first);
+            let composed = match second {
+                Some(second) => values(second).1,
+                None => apply(intermediate, &nested(Side::Second)),
+            };
+            (original, composed)
+        }
+        // the second list replaced the whole value
+        (None, Some(second)) => {
+            let (intermediate, composed) = values(second);
+            let original = apply(intermediate, &invert(&nested(Side::First)));
+            (original, composed)
+        }
+        // the first list removed the value and the second one added it back
+        (None, None) => {
+            let (first, second) = (nested(Side::First), nested(Side::Second));
+            if !only(&first, Operation::Delete) || !only(&second, Operation::Add) {
+                return group.iter().map(|(_, _, delta)| (*delta).clone()).collect();
+            }
+            (apply(None, &invert(&first)), apply(None, &second))
+        }
+    };
+
+    let path = path.to_string();
+    let delta = match (original, composed) {
+        (None, None) => return vec![],
+        (None, Some(value)) => Delta::new(Operation::Add, path, Value::Null, value),
+        (Some(value), None) => Delta::new(Operation::Delete, path, value, Value::Null),
+        (Some(old), Some(new)) if old == new => return vec![],
+        (Some(old), Some(new)) => Delta::new(Operation::Change, path, old, new),
+    };
+
+    vec![delta]
+}
+
+/// Returns the value before and after the delta, `None` where absent
+fn values(delta: &Delta) -> (Option<Value>, Option<Value>) {
+    match delta.operation {
+        Operation::Add => (None, Some(delta.new_value.clone())),
+        Operation::Change => (Some(delta.old_value.clone()), Some(delta.new_value.clone())),
+        Operation::Delete => (Some(delta.old_value.clone()), None),
+    }
+}
+
+/// Applies deltas relative to a value, starting from an empty object or
+/// array, as the first delta expects, when there is no value.
+fn apply(value: Option<Value>, deltas: &[Delta]) -> Option<Value> {
+    let value = value.unwrap_or_else(|| match deltas.first() {
+        Some(delta) if matches!(segments(&delta.path).next(), Some(Segment::Index(_))) => {
+            json!([])
+        }
+        _ => json!({}),
+    });
+
+    Some(patch(value, deltas, PatchOptions::default()))
+}
+
+/// Rewrites the path of a delta nested in `root` to be relative to it
+fn relative(delta: &Delta, root: &str) -> Delta {
+    let mut delta = delta.clone();
+    delta.path = format!("${}", &delta.path[root.len()..]);
+    delta
+}
@@ -0,0 +1,92 @@
+#[cfg(test)]
+mod tests {
+    use crate::compose::compose;
+    use crate::delta::Delta;
+    use crate::delta::Operation::{Add, Change, Delete};
+    use crate::differ::Differ;
+    use crate::patcher::{patch, PatchOptions};
+    use serde_json::{json, Value, Value::Null};
+
+    fn diff(left: &Value, right: &Value) -> Vec<Delta> {
+        let mut differ = Differ::new_from_json_values(left.clone(), right.clone());
+        differ.diff().get_deltas().clone()
+    }
+
+    #[test]
+    fn compose_add_then_delete() {
+        let first = vec![Delta::new(Add, "$.a".to_string(), Null, json!(1))];
+        let second = vec![Delta::new(Delete, "$.a".to_string(), json!(1), Null)];
+
+        assert_eq!(compose(&first, &second), vec![]);
+    }
+
+    #[test]
+    fn compose_changes() {
+        let first = vec![Delta::new(Change, "$.a".to_string(), json!(1), json!(2))];
+        let second = vec![
+            Delta::new(Change, "$.a".to_string(), json!(2), json!(3)),
+            Delta::new(Add, "$.b".to_string(), Null, json!(true)),
+        ];
+
+        assert_eq!(
+            compose(&first, &second),
+            vec![
+                Delta::new(Change, "$.a".to_string(), json!(1), json!(3)),
+                Delta::new(Add, "$.b".to_string(), Null, json!(true)),
+            ]
+        );
+
+        let second = vec![Delta::new(Change, "$.a".to_string(), json!(2), json!(1))];
+        assert_eq!(compose(&first, &second), vec![]);
+    }
+
+    #[test]
+    fn compose_delete_then_add() {
+        let first = vec![Delta::new(Delete, "$.a[1]".to_string(), json!("x"), Null)];
+        let second = vec![Delta::new(Add, "$.a[1]".to_string(), Null, json!("y"))];
+
+        assert_eq!(
+            compose(&first, &second),
+            vec![Delta::new(
+                Change,
+                "$.a[1]".to_string(),
+                json!("x"),
+                json!("y")
+            )]
+        );
+    }
+
+    #[test]
+    fn compose_nested_paths() {
+        let cases = vec![
+            (
+                json!({"a": {"b": 1, "c": [1, 2]}}),
+                json!({"a": {"b": 2, "c": [1, 2, 3]}}),
+                json!({"a": 1}),
+            ),
+            (
+                json!({"a": 1}),
+                json!({"a": {"b": {"c": 1}}}),
+                json!({"a": {"b": {"c": 2, "d": []}}}),
+            ),
+            (
+                json!({"a": [1, 2, 3]}),
+                json!({"a": [1]}),
+                json!({"a": [1, {"b": 2}]}),
+            ),
+            (json!({"a": {"b": 1}}), json!({}), json!({"a": [1]})),
+        ];
+
+        for (a, b, c) in cases {
+            let composed = compose(&diff(&a, &b), &diff(&b, &c));
+            assert_eq!(
+                patch(a.clone(), &composed, PatchOptions::default()),
+                c,
+                "{} -> {} -> {}",
+                a,
+                b,
+                c
+            );
+        }
+    }
+}
@@ -1,6 +1,7 @@
-use crate::utils::calculate_hash;
+use crate::utils::{calculate_hash, segments};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::cmp::Ordering;
 use std::hash::{Hash, Hasher};
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
@@ -24,6 +25,22 @@ impl Hash for Operation {
     }
 }
 
+/// A position in a source text, lines and columns start at 1 and columns
+/// count characters
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Where a value is written in a source text, `end` is past its last
+/// character
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Delta {
     pub operation: Operation,
@@ -31,6 +48,13 @@ pub struct Delta {
     pub old_value: Value,
     pub new_value: Value,
     pub hash: String,
+    /// The span of the old value in the left text, see
+    /// `Differ::new_from_json_text`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub left_span: Option<Span>,
+    /// The span of the new value in the right text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub right_span: Option<Span>,
 }
 
 impl Delta {
@@ -41,10 +65,55 @@ impl Delta {
             old_value,
             new_value,
             hash: String::default(),
+            left_span: None,
+            right_span: None,
         };
         delta.hash = calculate_hash(&delta).to_string();
         delta
     }
+
+    /// Returns the delta undoing this one: additions become deletions and the
+    /// old and new values are swapped
+    pub fn invert(&self) -> Delta {
+        let operation = match self.operation {
+            Operation::Add => Operation::Delete,
+            Operation::Change => Operation::Change,
+            Operation::Delete => Operation::Add,
+        };
+        Delta::new(
+            operation,
+            self.path.clone(),
+            self.new_value.clone(),
+            self.old_value.clone(),
+        )
+    }
+}
+
+/// Returns the deltas undoing the given ones, in reverse order, so that
+/// patching `b` with `invert(diff(a, b))` gives back `a`
+pub fn invert(deltas: &[Delta]) -> Vec<Delta> {
+    deltas.iter().rev().map(Delta::invert).collect()
+}
+
+/// A delta borrowing its path and values, see `Differ::visit`
+#[derive(Debug, PartialEq, Clone)]
+pub struct DeltaRef<'a> {
+    pub operation: Operation,
+    pub path: &'a str,
+    pub old_value: &'a Value,
+    pub new_value: &'a Value,
+}
+
+impl DeltaRef<'_> {
+    /// Clones the path and values into an owned delta
+    pub fn to_delta(&self) -> Delta {
+        Delta::new(
+            self.operation.clone(),
+            self.path.to_string(),
+            self.old_value.clone(),
+            self.new_value.clone(),
+        )
+    }
 }
 
 impl Hash for Delta {
@@ -56,6 +125,76 @@ impl Hash for Delta {
     }
 }
 
+/// Compares two delta paths segment by segment: object keys by their bytes
+/// and array indices numerically, so `$.a[2]` comes before `$.a[10]` and a
+/// path comes before the paths nested in it.
+pub fn compare_paths(a: &str, b: &str) -> Ordering {
+    segments(a).cmp(segments(b))
+}
+
+/// Compact serialization of delta lists, to use with `#[serde(with = ...)]`.
+///
+/// Each delta is a sequence without field names or hash: `[0, path, new]` for
+/// an addition, `[1, path, old, new]` for a change and `[2, path, old]` for a
+/// deletion. Hashes are computed again when reading the deltas.
+pub mod compact {
+    use super::{Delta, Operation};
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use serde_json::Value;
+
+    #[derive(Serialize)]
+    #[serde(untagged)]
+    enum EntryRef<'a> {
+        Change(u8, &'a str, &'a Value, &'a Value),
+        Other(u8, &'a str, &'a Value),
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Entry {
+        Change(u8, String, Value, Value),
+        Other(u8, String, Value),
+    }
+
+    /// Borrowed delta list that serializes in the compact form, for formats
+    /// written without a wrapping struct
+    #[derive(Serialize)]
+    pub struct CompactRef<'a>(#[serde(with = "super::compact")] pub &'a [Delta]);
+
+    /// Delta list that deserializes from the compact form
+    #[derive(Deserialize)]
+    pub struct Compact(#[serde(with = "super::compact")] pub Vec<Delta>);
+
+    pub fn serialize<S: Serializer>(deltas: &[Delta], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(deltas.iter().map(|delta| match delta.operation {
+            Operation::Add => EntryRef::Other(0, &delta.path, &delta.new_value),
+            Operation::Change => {
+                EntryRef::Change(1, &delta.path, &delta.old_value, &delta.new_value)
+            }
+            Operation::Delete => EntryRef::Other(2, &delta.path, &delta.old_value),
+        }))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Delta>, D::Error> {
+        Vec::<Entry>::deserialize(deserializer)?
+            .into_iter()
+            .map(|entry| match entry {
+                Entry::Other(0, path, value) => {
+                    Ok(Delta::new(Operation::Add, path, Value::Null, value))
+                }
+                Entry::Change(1, path, old, new) => {
+                    Ok(Delta::new(Operation::Change, path, old, new))
+                }
+                Entry::Other(2, path, value) => {
+                    Ok(Delta::new(Operation::Delete, path, value, Value::Null))
+                }
+                _ => Err(D::Error::custom("invalid compact delta")),
+            })
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -70,4 +209,57 @@ mod tests {
         );
         assert_eq!(delta.hash, "13989947290824433245");
     }
+
+    #[test]
+    fn test_compare_paths() {
+        let mut paths = vec![
+            "$.b",
+            "$.a[10]",
+            "$.a[2].c",
+            "$.a[2]",
+            "$.a[2][1]",
+            "$.ab",
+            "$.a.b",
+            "$",
+        ];
+        paths.sort_by(|a, b| compare_paths(a, b));
+
+        assert_eq!(
+            paths,
+            vec![
+                "$",
+                "$.a[2]",
+                "$.a[2][1]",
+                "$.a[2].c",
+                "$.a[10]",
+                "$.a.b",
+                "$.ab",
+                "$.b"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compact_deltas() {
+        #[derive(Serialize, Deserialize)]
+        struct Deltas(#[serde(with = "compact")] Vec<Delta>);
+
+        let deltas = vec![
+            Delta::new(Operation::Add, "$.a".to_string(), Value::Null, 1.into()),
+            Delta::new(Operation::Change, "$.b".to_string(), 1.into(), 2.into()),
+            Delta::new(
+                Operation::Delete,
+                "$.c[0]".to_string(),
+                "x".into(),
+                Value::Null,
+            ),
+        ];
+
+        let text = serde_json::to_string(&Deltas(deltas.clone())).unwrap();
+        assert_eq!(text, r#"[[0,"$.a",1],[1,"$.b",1,2],[2,"$.c[0]","x"]]"#);
+
+        let Deltas(read) = serde_json::from_str(&text).unwrap();
+        assert_eq!(read, deltas);
+        assert!(serde_json::from_str::<Deltas>(r#"[[1,"$.a",1]]"#).is_err());
+    }
 }
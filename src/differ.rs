@@ -1,15 +1,55 @@
-use crate::delta::{Delta, Operation};
-use crate::utils::calculate_hash;
+use crate::delta::{compare_paths, Delta, DeltaRef, Operation, Span};
+use crate::errors::ProcessError;
+use crate::schema::{ClassifiedDelta, Schema};
+use crate::text;
+use crate::utils::{push_index, push_key};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use serde_json_path::JsonPath;
-use std::collections::{HashMap, VecDeque};
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+
+static NULL: Value = Value::Null;
+
+/// Receives the differences found by `Differ::visit`
+pub trait DiffVisitor {
+    /// Called for every difference, returning `ControlFlow::Break` stops the walk
+    fn visit(&mut self, delta: DeltaRef<'_>) -> ControlFlow<()>;
+}
+
+impl<F> DiffVisitor for F
+where
+    F: FnMut(DeltaRef<'_>) -> ControlFlow<()>,
+{
+    fn visit(&mut self, delta: DeltaRef<'_>) -> ControlFlow<()> {
+        self(delta)
+    }
+}
+
+/// Order of the deltas returned by `Differ::diff`
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum DeltaOrder {
+    /// Sorted with `compare_paths`: object keys by their bytes and array
+    /// indices numerically. The order does not depend on the order of the
+    /// keys in the documents.
+    #[default]
+    Path,
+    /// The order the documents are walked in. Keys of the left document come
+    /// first, in map order, followed by the keys only found in the right one.
+    /// Map order is sorted by default and follows the document with the
+    /// `preserve_order` feature of `serde_json`.
+    Document,
+}
 
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Differ {
     deltas: Vec<Delta>,
     left: Value,
     right: Value,
+    #[serde(default)]
+    order: DeltaOrder,
+    /// Spans of the values of the left and right texts, by path
+    #[serde(skip)]
+    spans: (HashMap<String, Span>, HashMap<String, Span>),
 }
 
 impl Default for Differ {
@@ -18,6 +58,8 @@ impl Default for Differ {
             deltas: Vec::new(),
             left: Value::Null,
             right: Value::Null,
+            order: DeltaOrder::default(),
+            spans: Default::default(),
         }
     }
 }
@@ -28,14 +70,86 @@ impl Differ {
             left: a,
             right: b,
             deltas: Vec::new(),
+            order: DeltaOrder::default(),
+            spans: Default::default(),
         }
     }
 
+    /// Parses two JSON texts to compare. The deltas of `diff` carry the line
+    /// and column span of their old value in the left text and of their new
+    /// value in the right one. Comments and trailing commas are accepted.
+    pub fn new_from_json_text(left: &str, right: &str) -> Result<Differ, ProcessError> {
+        let (left_node, right_node) = (text::parse(left)?, text::parse(right)?);
+        let mut differ = Differ::new_from_json_values(left_node.value(), right_node.value());
+        differ.spans = (
+            text::spans(left, &left_node),
+            text::spans(right, &right_node),
+        );
+        Ok(differ)
+    }
+
+    /// Parses two YAML documents to compare
+    #[cfg(feature = "yaml")]
+    pub fn new_from_yaml(left: &str, right: &str) -> Result<Differ, ProcessError> {
+        Ok(Differ::new_from_json_values(
+            crate::yaml::from_str(left)?,
+            crate::yaml::from_str(right)?,
+        ))
+    }
+
+    /// Decodes two CBOR documents to compare
+    #[cfg(feature = "cbor")]
+    pub fn new_from_cbor(left: &[u8], right: &[u8]) -> Result<Differ, ProcessError> {
+        Ok(Differ::new_from_json_values(
+            crate::cbor::from_slice(left)?,
+            crate::cbor::from_slice(right)?,
+        ))
+    }
+
+    /// Parses two JSON5 or JSONC documents to compare
+    #[cfg(feature = "json5")]
+    pub fn new_from_json5(left: &str, right: &str) -> Result<Differ, ProcessError> {
+        Ok(Differ::new_from_json_values(
+            crate::json5::from_str(left)?,
+            crate::json5::from_str(right)?,
+        ))
+    }
+
+    /// Decodes two MessagePack documents to compare
+    #[cfg(feature = "msgpack")]
+    pub fn new_from_msgpack(left: &[u8], right: &[u8]) -> Result<Differ, ProcessError> {
+        Ok(Differ::new_from_json_values(
+            crate::msgpack::from_slice(left)?,
+            crate::msgpack::from_slice(right)?,
+        ))
+    }
+
+    /// Parses two TOML documents to compare
+    #[cfg(feature = "toml")]
+    pub fn new_from_toml(left: &str, right: &str) -> Result<Differ, ProcessError> {
+        Ok(Differ::new_from_json_values(
+            crate::toml::from_str(left)?,
+            crate::toml::from_str(right)?,
+        ))
+    }
+
+    /// Sets the order of the deltas returned by `diff`
+    pub fn order(mut self, order: DeltaOrder) -> Self {
+        self.order = order;
+        self
+    }
+
     /// Returns the deltas between the two values
     pub fn get_deltas(&self) -> &Vec<Delta> {
         &self.deltas
     }
 
+    /// Classifies the deltas against a schema the right value is expected to
+    /// match, see `schema::classify`
+    pub fn classify(&self, schema: &Schema) -> Vec<ClassifiedDelta> {
+        crate::schema::classify(schema, &self.right, &self.deltas)
+    }
+
     /// Returns the delta for the given path
     pub fn get_delta_by_path(&self, path: &str) -> Option<&Delta> {
         self.deltas.iter().find(|delta| delta.path == path)
@@ -53,138 +167,264 @@ impl Differ {
         !self.deltas.is_empty()
     }
 
-    /// Returns true if the two values are equal
-    pub fn diff(&mut self) -> &Self {
-        let mut seen: HashMap<String, bool> = HashMap::new();
-        let mut deltas: Vec<Delta> = Vec::new();
-
-        let mut calculated_hash: Vec<String> = vec![];
+    /// Returns true if `diff` would not produce any delta for the two values.
+    ///
+    /// Both documents are walked together and the walk stops at the first
+    /// difference, so no delta is built along the way.
+    pub fn is_equal(&self) -> bool {
+        let mut path = String::from("$");
+        walk(&self.left, &self.right, &mut path, &mut |_, _, _, _| {
+            ControlFlow::Break(())
+        })
+        .is_continue()
+    }
 
-        self.do_diff(
+    /// Compares the two values and hands every difference to the visitor,
+    /// without storing them.
+    ///
+    /// The deltas borrow the values of the differ, so nothing is cloned unless
+    /// the visitor keeps them with `DeltaRef::to_delta`. Differences come in
+    /// `DeltaOrder::Document` order, and the walk stops as soon as the visitor
+    /// returns `ControlFlow::Break`.
+    pub fn visit<V: DiffVisitor>(&self, visitor: &mut V) {
+        let mut path = String::from("$");
+        let _ = walk(
             &self.left,
             &self.right,
-            &mut deltas,
-            &mut seen,
-            false,
-            &mut calculated_hash,
+            &mut path,
+            &mut |operation, path, old_value, new_value| {
+                visitor.visit(DeltaRef {
+                    operation,
+                    path,
+                    old_value,
+                    new_value,
+                })
+            },
         );
+    }
 
-        self.do_diff(
-            &self.right,
-            &self.left,
-            &mut deltas,
-            &mut seen,
-            true,
-            &mut calculated_hash,
-        );
+    /// Compares the two values and stores the deltas, in the order set with
+    /// `order`.
+    ///
+    /// With the `parallel` feature the children of large objects and arrays
+    /// are compared on the rayon thread pool. The deltas are the same, in the
+    /// same order, as without the feature.
+    pub fn diff(&mut self) -> &Self {
+        #[cfg(not(feature = "parallel"))]
+        let mut deltas = walk_sequential(&self.left, &self.right, "$".to_string());
 
-        self.deltas = deltas;
+        #[cfg(feature = "parallel")]
+        let mut deltas = walk_parallel(&self.left, &self.right, "$");
+
+        if self.order == DeltaOrder::Path {
+            deltas.sort_by(|a, b| compare_paths(&a.path, &b.path));
+        }
+
+        let (left_spans, right_spans) = &self.spans;
+        if !left_spans.is_empty() {
+            for delta in &mut deltas {
+                if delta.operation != Operation::Add {
+                    delta.left_span = left_spans.get(&delta.path).copied();
+                }
+                if delta.operation != Operation::Delete {
+                    delta.right_span = right_spans.get(&delta.path).copied();
+                }
+            }
+        }
 
-        self.deltas.reverse();
+        self.deltas = deltas;
 
         self
     }
+}
+
+fn push_delta(
+    deltas: &mut Vec<Delta>,
+    operation: Operation,
+    path: &str,
+    old_value: &Value,
+    new_value: &Value,
+) {
+    deltas.push(Delta::new(
+        operation,
+        path.to_string(),
+        old_value.clone(),
+        new_value.clone(),
+    ));
+}
+
+/// Collects the deltas between the two values on the current thread
+fn walk_sequential(left: &Value, right: &Value, mut path: String) -> Vec<Delta> {
+    let mut deltas = Vec::new();
+    let _ = walk(left, right, &mut path, &mut |operation, path, old, new| {
+        push_delta(&mut deltas, operation, path, old, new);
+        ControlFlow::Continue(())
+    });
+    deltas
+}
+
+/// Number of entries an object or array needs before its children are
+/// compared on separate threads.
+#[cfg(feature = "parallel")]
+const PARALLEL_THRESHOLD: usize = 64;
+
+/// Same as `walk_sequential`, but the children of large objects and arrays
+/// are compared on the rayon thread pool. Results are joined in the order of
+/// the children, so the output does not depend on thread scheduling.
+#[cfg(feature = "parallel")]
+fn walk_parallel(left: &Value, right: &Value, path: &str) -> Vec<Delta> {
+    use rayon::prelude::*;
+
+    let children: Vec<Vec<Delta>> = match (left, right) {
+        (Value::Object(left_map), Value::Object(right_map))
+            if left_map.len().min(right_map.len()) > 0
+                && left_map.len().max(right_map.len()) >= PARALLEL_THRESHOLD =>
+        {
+            let entries: Vec<(&String, Option<&Value>, Option<&Value>)> = left_map
+                .iter()
+                .map(|(key, value)| (key, Some(value), right_map.get(key)))
+                .chain(
+                    right_map
+                        .iter()
+                        .filter(|(key, _)| !left_map.contains_key(*key))
+                        .map(|(key, value)| (key, None, Some(value))),
+                )
+                .collect();
+
+            entries
+                .into_par_iter()
+                .map(|(key, left, right)| {
+                    let mut path = path.to_string();
+                    push_key(&mut path, key);
+                    walk_child(left, right, path)
+                })
+                .collect()
+        }
+        (Value::Array(left_array), Value::Array(right_array))
+            if left_array.len().min(right_array.len()) > 0
+                && left_array.len().max(right_array.len()) >= PARALLEL_THRESHOLD =>
+        {
+            (0..left_array.len().max(right_array.len()))
+                .into_par_iter()
+                .map(|index| {
+                    walk_child(
+                        left_array.get(index),
+                        right_array.get(index),
+                        format!("{}[{}]", path, index),
+                    )
+                })
+                .collect()
+        }
+        _ => return walk_sequential(left, right, path.to_string()),
+    };
+
+    children.into_iter().flatten().collect()
+}
+
+/// Collects the deltas of a child that exists on one or both sides, a child
+/// that only exists on one side being reported whole
+#[cfg(feature = "parallel")]
+fn walk_child(left: Option<&Value>, right: Option<&Value>, path: String) -> Vec<Delta> {
+    match (left, right) {
+        (Some(left), Some(right)) => walk_parallel(left, right, &path),
+        (Some(left), None) => vec![Delta::new(
+            Operation::Delete,
+            path,
+            left.clone(),
+            Value::Null,
+        )],
+        (None, Some(right)) => vec![Delta::new(Operation::Add, path, Value::Null, right.clone())],
+        (None, None) => Vec::new(),
+    }
+}
+
+/// Walks both values at the same time and calls `emit` for every difference.
+///
+/// Keys and array elements that only exist on one side are reported whole,
+/// so an `Add` or `Delete` always inserts or removes a single key or element,
+/// array elements being added or removed at the end of their array. Values
+/// of a different kind, and containers that become empty or stop being empty,
+/// are reported as a single change of the whole value, except for the root.
+fn walk<F>(left: &Value, right: &Value, path: &mut String, emit: &mut F) -> ControlFlow<()>
+where
+    F: FnMut(Operation, &str, &Value, &Value) -> ControlFlow<()>,
+{
+    let length = path.len();
+
+    if is_emptied(left, right) && path.len() > 1 {
+        return emit(Operation::Change, path, left, right);
+    }
 
-    /// Compares the two values and adds the deltas to the given vector
-    fn do_diff(
-        &self,
-        left: &Value,
-        right: &Value,
-        deltas: &mut Vec<Delta>,
-        seen: &mut HashMap<String, bool>,
-        reverse: bool,
-        calculated_hash: &mut Vec<String>,
-    ) {
-        let current_path = Vec::new();
-        let mut stack: VecDeque<(&Value, &Value, Vec<String>)> = VecDeque::new();
-        stack.push_back((left, right, current_path));
-
-        while let Some((left, right, path)) = stack.pop_back() {
-            match left {
-                Value::Object(map) => {
-                    for (key, value) in map.iter() {
-                        let mut new_path = path.clone();
-                        if path.is_empty() {
-                            new_path.push(format!("$.{}", key));
-                        } else {
-                            new_path.push(key.to_string());
-                        }
-                        stack.push_back((value, right, new_path));
-                    }
+    match (left, right) {
+        (Value::Object(left_map), Value::Object(right_map)) => {
+            for (key, value) in left_map.iter() {
+                push_key(path, key);
+                match right_map.get(key) {
+                    Some(other) => walk(value, other, path, emit)?,
+                    None => walk_whole(Operation::Delete, value, path, emit)?,
                 }
-                Value::Array(array) => {
-                    for (index, value) in array.iter().enumerate() {
-                        let mut new_path = path.clone();
-                        match path.len() {
-                            0 => new_path.push(format!("$[{}]", index)),
-                            _ => new_path.push(format!("[{}]", index)),
-                        }
-                        stack.push_back((value, right, new_path));
-                    }
+                path.truncate(length);
+            }
+
+            for (key, value) in right_map.iter() {
+                if left_map.contains_key(key) {
+                    continue;
                 }
-                _ => {
-                    let mut path_str = String::new();
-                    for (index, value) in path.iter().enumerate() {
-                        if value.contains('[') || index == 0 {
-                            path_str.push_str(value);
-                        } else {
-                            path_str.push_str(format!(".{}", value).as_str());
-                        }
-                    }
-
-                    if seen.contains_key(path_str.as_str()) {
-                        continue;
-                    }
-
-                    let parsed_path = JsonPath::parse(path_str.as_str()).unwrap();
-                    let right_value = parsed_path.query(right).first();
-                    match right_value {
-                        Some(value) => {
-                            if left != value {
-                                let mut delta = Delta {
-                                    operation: Operation::Change,
-                                    path: path_str.clone(),
-                                    old_value: left.clone(),
-                                    new_value: value.clone(),
-                                    hash: String::default(),
-                                };
-
-                                delta.hash = calculate_hash(&delta).to_string();
-                                calculated_hash.push(delta.hash.clone());
-                                deltas.push(delta);
-                            }
-                        }
-                        None => {
-                            if reverse {
-                                let mut delta = Delta {
-                                    operation: Operation::Add,
-                                    path: path_str.clone(),
-                                    old_value: Value::Null,
-                                    new_value: left.clone(),
-                                    hash: String::default(),
-                                };
-                                delta.hash = calculate_hash(&delta).to_string();
-                                calculated_hash.push(delta.hash.clone());
-                                deltas.push(delta);
-                                continue;
-                            }
-
-                            let mut delta = Delta {
-                                operation: Operation::Delete,
-                                path: path_str.clone(),
-                                old_value: left.clone(),
-                                new_value: Value::Null,
-                                hash: String::default(),
-                            };
-                            delta.hash = calculate_hash(&delta).to_string();
-                            calculated_hash.push(delta.hash.clone());
-                            deltas.push(delta);
-                        }
-                    }
-
-                    seen.insert(path_str.clone(), true);
+                push_key(path, key);
+                walk_whole(Operation::Add, value, path, emit)?;
+                path.truncate(length);
+            }
+        }
+        (Value::Array(left_array), Value::Array(right_array)) => {
+            for (index, value) in left_array.iter().enumerate() {
+                push_index(path, index);
+                match right_array.get(index) {
+                    Some(other) => walk(value, other, path, emit)?,
+                    None => emit(Operation::Delete, path, value, &NULL)?,
                 }
+                path.truncate(length);
+            }
+
+            for (index, value) in right_array.iter().enumerate().skip(left_array.len()) {
+                push_index(path, index);
+                emit(Operation::Add, path, &NULL, value)?;
+                path.truncate(length);
+            }
+        }
+        (Value::Object(_) | Value::Array(_), _) | (_, Value::Object(_) | Value::Array(_)) => {
+            emit(Operation::Change, path, left, right)?;
+        }
+        _ => {
+            if left != right {
+                emit(Operation::Change, path, left, right)?;
             }
         }
     }
+
+    ControlFlow::Continue(())
+}
+
+/// Calls `emit` once for a whole value that only exists on one side
+fn walk_whole<F>(operation: Operation, value: &Value, path: &str, emit: &mut F) -> ControlFlow<()>
+where
+    F: FnMut(Operation, &str, &Value, &Value) -> ControlFlow<()>,
+{
+    match operation {
+        Operation::Add => emit(operation, path, &NULL, value),
+        _ => emit(operation, path, value, &NULL),
+    }
+}
+
+/// Returns true if both values are objects, or both arrays, and only one of
+/// them is empty
+pub(crate) fn is_emptied(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Object(left_map), Value::Object(right_map)) => {
+            left_map.is_empty() != right_map.is_empty()
+        }
+        (Value::Array(left_array), Value::Array(right_array)) => {
+            left_array.is_empty() != right_array.is_empty()
+        }
+        _ => false,
+    }
 }
@@ -1,9 +1,10 @@
 #[cfg(test)]
 mod tests {
-    use crate::delta::Operation;
-    use crate::differ::Differ;
+    use crate::delta::{compare_paths, DeltaRef, Operation, Position, Span};
+    use crate::differ::{DeltaOrder, DiffVisitor, Differ};
     use serde_json::{json, Value};
     use std::fs;
+    use std::ops::ControlFlow;
     use std::path::PathBuf;
 
     #[test]
@@ -210,24 +211,12 @@ mod tests {
         let differ = differ.diff();
         let deltas = differ.get_deltas();
 
-        assert_eq!(deltas.len(), 3, "Expected 1 deltas, got {}", deltas.len());
+        assert_eq!(deltas.len(), 1, "Expected 1 deltas, got {}", deltas.len());
 
         let delta = deltas.first().unwrap();
-        assert_eq!(delta.path, "$.test[0]");
-        assert_eq!(delta.operation, Operation::Delete);
-        assert_eq!(delta.old_value, Value::Number(1.into()));
-        assert_eq!(delta.new_value, Value::Null);
-
-        let delta = deltas.get(1).unwrap();
-        assert_eq!(delta.path, "$.test[1]");
+        assert_eq!(delta.path, "$.test");
         assert_eq!(delta.operation, Operation::Delete);
-        assert_eq!(delta.old_value, Value::Number(2.into()));
-        assert_eq!(delta.new_value, Value::Null);
-
-        let delta = deltas.get(2).unwrap();
-        assert_eq!(delta.path, "$.test[2]");
-        assert_eq!(delta.operation, Operation::Delete);
-        assert_eq!(delta.old_value, Value::Number(3.into()));
+        assert_eq!(delta.old_value, json!([1, 2, 3]));
         assert_eq!(delta.new_value, Value::Null);
     }
 
@@ -257,28 +246,16 @@ mod tests {
 
         assert_eq!(
             diff.get_deltas().len(),
-            3,
+            1,
             "Expected 1 deltas, got {}",
             diff.get_deltas().len()
         );
 
         let delta = diff.get_deltas().first().unwrap();
-        assert_eq!(delta.path, "$.test[0]");
-        assert_eq!(delta.operation, Operation::Add);
-        assert_eq!(delta.old_value, Value::Null);
-        assert_eq!(delta.new_value, Value::Number(1.into()));
-
-        let delta = diff.get_deltas().get(1).unwrap();
-        assert_eq!(delta.path, "$.test[1]");
-        assert_eq!(delta.operation, Operation::Add);
-        assert_eq!(delta.old_value, Value::Null);
-        assert_eq!(delta.new_value, Value::Number(2.into()));
-
-        let delta = diff.get_deltas().get(2).unwrap();
-        assert_eq!(delta.path, "$.test[2]");
+        assert_eq!(delta.path, "$.test");
         assert_eq!(delta.operation, Operation::Add);
         assert_eq!(delta.old_value, Value::Null);
-        assert_eq!(delta.new_value, Value::Number(3.into()));
+        assert_eq!(delta.new_value, json!([1, 2, 3]));
     }
 
     #[test]
@@ -331,4 +308,302 @@ mod tests {
         assert_eq!(delta.old_value, Value::String("2".to_string()));
         assert_eq!(delta.new_value, Value::String("3".to_string()));
     }
+
+    #[test]
+    fn is_equal_matches_diff() {
+        let cases = vec![
+            (json!({"a": 1}), json!({"a": 1}), true),
+            (json!({"a": 1}), json!({"a": 2}), false),
+            (json!({"a": 1}), json!({"a": 1, "b": 2}), false),
+            (json!({"a": [1, 2, 3]}), json!({"a": [1, 2]}), false),
+            (
+                json!({"a": {"b": [1, {"c": "d"}]}}),
+                json!({"a": {"b": [1, {"c": "e"}]}}),
+                false,
+            ),
+            (json!({"a": 1}), json!({"a": {"b": 1}}), false),
+            (json!({"a": {}}), json!({"a": 1}), false),
+            (json!({"a": 1}), json!({"a": 1, "b": {}}), false),
+            (json!({"a": [[], {}]}), json!({"a": []}), false),
+        ];
+
+        for (left, right, expected) in cases {
+            let mut differ = Differ::new_from_json_values(left.clone(), right.clone());
+            assert_eq!(differ.is_equal(), expected, "{} vs {}", left, right);
+            assert_eq!(
+                !differ.diff().has_changes(),
+                expected,
+                "{} vs {}",
+                left,
+                right
+            );
+        }
+    }
+
+    #[test]
+    fn diff_type_change() {
+        let a = json!({"a": {"b": [1, 2]}, "c": 1});
+        let b = json!({"a": 1, "c": {"d": true}});
+
+        let mut differ = Differ::new_from_json_values(a, b);
+        let deltas = differ.diff().get_deltas();
+
+        assert_eq!(deltas.len(), 2, "Expected 2 deltas, got {}", deltas.len());
+
+        assert_eq!(deltas[0].path, "$.a");
+        assert_eq!(deltas[0].operation, Operation::Change);
+        assert_eq!(deltas[0].old_value, json!({"b": [1, 2]}));
+        assert_eq!(deltas[0].new_value, json!(1));
+
+        assert_eq!(deltas[1].path, "$.c");
+        assert_eq!(deltas[1].operation, Operation::Change);
+        assert_eq!(deltas[1].old_value, json!(1));
+        assert_eq!(deltas[1].new_value, json!({"d": true}));
+    }
+
+    #[test]
+    fn diff_large_documents_order() {
+        let mut left = serde_json::Map::new();
+        let mut right = serde_json::Map::new();
+
+        for index in 0..104 {
+            let values: Vec<usize> = (index * 100..index * 100 + 100).collect();
+            if index < 100 {
+                left.insert(format!("key_{:03}", index), json!(values));
+            }
+
+            let mut values = values;
+            if index >= 100 {
+                values.truncate(1);
+            } else if index % 10 == 0 {
+                values[index] += 1;
+            }
+            right.insert(format!("key_{:03}", index), json!(values));
+        }
+
+        let expected: Vec<String> = (0..100)
+            .step_by(10)
+            .map(|index| format!("$.key_{:03}[{}]", index, index))
+            .chain((100..104).map(|index| format!("$.key_{:03}", index)))
+            .collect();
+
+        for _ in 0..3 {
+            let mut differ = Differ::new_from_json_values(
+                Value::Object(left.clone()),
+                Value::Object(right.clone()),
+            );
+            let paths: Vec<String> = differ
+                .diff()
+                .get_deltas()
+                .iter()
+                .map(|delta| delta.path.clone())
+                .collect();
+
+            assert_eq!(paths, expected);
+        }
+    }
+
+    #[test]
+    fn visit_deltas() {
+        let a = json!({"a": 1, "b": [1, 2, 3], "c": "d"});
+        let b = json!({"a": 2, "b": [1], "e": true});
+
+        let mut differ = Differ::new_from_json_values(a, b);
+
+        let mut visited = Vec::new();
+        differ.visit(&mut |delta: DeltaRef<'_>| {
+            visited.push(delta.to_delta());
+            ControlFlow::Continue(())
+        });
+
+        let mut expected = differ.diff().get_deltas().clone();
+        visited.sort_by(|a, b| a.path.cmp(&b.path));
+        expected.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(visited, expected);
+
+        let mut count = 0;
+        differ.visit(&mut |delta: DeltaRef<'_>| {
+            count += 1;
+            if delta.operation == Operation::Delete {
+                return ControlFlow::Break(());
+            }
+            ControlFlow::Continue(())
+        });
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn visit_with_visitor() {
+        struct Counter {
+            added: usize,
+            changed: usize,
+            deleted: usize,
+        }
+
+        impl DiffVisitor for Counter {
+            fn visit(&mut self, delta: DeltaRef<'_>) -> ControlFlow<()> {
+                match delta.operation {
+                    Operation::Add => self.added += 1,
+                    Operation::Change => self.changed += 1,
+                    Operation::Delete => self.deleted += 1,
+                }
+                ControlFlow::Continue(())
+            }
+        }
+
+        let a = json!({"a": 1, "b": [1, 2, 3], "c": "d"});
+        let b = json!({"a": 2, "b": [1], "e": {"f": true, "g": null}});
+
+        let mut counter = Counter {
+            added: 0,
+            changed: 0,
+            deleted: 0,
+        };
+        Differ::new_from_json_values(a, b).visit(&mut counter);
+
+        assert_eq!(counter.added, 1);
+        assert_eq!(counter.changed, 1);
+        assert_eq!(counter.deleted, 3);
+    }
+
+    #[test]
+    fn diff_order() {
+        let a = json!({"c": [1, 2, 3], "a": 1, "b": {"x": 1}});
+        let b = json!({"a": 2, "aa": [true], "c": [1], "b": {"y": 1}});
+
+        let paths = |order: DeltaOrder| -> Vec<String> {
+            let mut differ = Differ::new_from_json_values(a.clone(), b.clone()).order(order);
+            differ
+                .diff()
+                .get_deltas()
+                .iter()
+                .map(|delta| delta.path.clone())
+                .collect()
+        };
+
+        assert_eq!(
+            paths(DeltaOrder::Path),
+            vec!["$.a", "$.aa", "$.b.x", "$.b.y", "$.c[1]", "$.c[2]"]
+        );
+        assert_eq!(
+            paths(DeltaOrder::Document),
+            vec!["$.a", "$.b.x", "$.b.y", "$.c[1]", "$.c[2]", "$.aa"]
+        );
+    }
+
+    #[test]
+    fn diff_whole_values() {
+        let a = json!({"list": [{"a": 1}], "old": {"x": [[1], {"y": 2}]}});
+        let b = json!({"list": [{"a": 1}, {"b": [2]}]});
+
+        let mut differ = Differ::new_from_json_values(a, b);
+        let deltas: Vec<(&str, &Value)> = differ
+            .diff()
+            .get_deltas()
+            .iter()
+            .map(|delta| match delta.operation {
+                Operation::Add => (delta.path.as_str(), &delta.new_value),
+                _ => (delta.path.as_str(), &delta.old_value),
+            })
+            .collect();
+
+        assert_eq!(
+            deltas,
+            vec![
+                ("$.list[1]", &json!({"b": [2]})),
+                ("$.old", &json!({"x": [[1], {"y": 2}]})),
+            ]
+        );
+    }
+
+    fn span(start: (usize, usize), end: (usize, usize)) -> Option<Span> {
+        Some(Span {
+            start: Position {
+                line: start.0,
+                column: start.1,
+            },
+            end: Position {
+                line: end.0,
+                column: end.1,
+            },
+        })
+    }
+
+    #[test]
+    fn diff_json_text_spans() {
+        let left = "{\n  \"name\": \"é\",\n  // kept\n  \"tags\": [1, 2],\n  \"old\": true\n}";
+        let right = "{\n  \"name\": \"e\",\n  \"tags\": [1, 2, {\n    \"a\": 3\n  }]\n}";
+
+        let mut differ = Differ::new_from_json_text(left, right).unwrap();
+        let spans: Vec<(&str, Option<Span>, Option<Span>)> = differ
+            .diff()
+            .get_deltas()
+            .iter()
+            .map(|delta| (delta.path.as_str(), delta.left_span, delta.right_span))
+            .collect();
+
+        assert_eq!(
+            spans,
+            vec![
+                ("$.name", span((2, 11), (2, 14)), span((2, 11), (2, 14))),
+                ("$.old", span((5, 10), (5, 14)), None),
+                ("$.tags[2]", None, span((3, 18), (5, 4))),
+            ]
+        );
+
+        let delta = serde_json::to_value(differ.get_delta_by_path("$.old").unwrap()).unwrap();
+        assert_eq!(
+            delta["left_span"],
+            json!({"start": {"line": 5, "column": 10}, "end": {"line": 5, "column": 14}})
+        );
+        assert!(delta.get("right_span").is_none());
+    }
+
+    #[test]
+    fn diff_json_values_have_no_spans() {
+        let mut differ = Differ::new_from_json_values(json!({"a": 1}), json!({"a": 2}));
+        let delta = &differ.diff().get_deltas()[0];
+        assert_eq!((delta.left_span, delta.right_span), (None, None));
+    }
+
+    #[test]
+    fn diff_large_documents_in_order() {
+        // large enough to be split across threads with the parallel feature
+        let mut left = serde_json::Map::new();
+        let mut right = serde_json::Map::new();
+        for index in 0..200 {
+            let key = format!("k{}", index);
+            match index % 4 {
+                0 => {
+                    left.insert(key, json!(index));
+                }
+                1 => {
+                    right.insert(key, json!({"nested": [index]}));
+                }
+                2 => {
+                    left.insert(key.clone(), json!(index));
+                    right.insert(key, json!(-index));
+                }
+                _ => {
+                    left.insert(key.clone(), json!((0..index).collect::<Vec<_>>()));
+                    right.insert(key, json!((1..index + 2).collect::<Vec<_>>()));
+                }
+            }
+        }
+        let (left, right) = (Value::Object(left), Value::Object(right));
+
+        let mut differ = Differ::new_from_json_values(left.clone(), right.clone());
+        let deltas = differ.diff().get_deltas();
+        assert!(deltas
+            .windows(2)
+            .all(|pair| compare_paths(&pair[0].path, &pair[1].path).is_lt()));
+
+        let mut visited = Vec::new();
+        differ.visit(&mut |delta: DeltaRef<'_>| {
+            visited.push(delta.to_delta());
+            ControlFlow::Continue(())
+        });
+        let mut differ = Differ::new_from_json_values(left, right).order(DeltaOrder::Document);
+        assert_eq!(differ.diff().get_deltas(), &visited);
+    }
 }
@@ -0,0 +1,229 @@
+//! Diff of two directories of JSON files.
+//!
+//! Files are paired by their path relative to each directory. Files found on
+//! one side only are reported as added or removed, and files found on both
+//! are compared with `Differ`, spread over one thread per core, or over the
+//! rayon thread pool with the `parallel` feature.
+
+use crate::delta::Delta;
+use crate::differ::Differ;
+use crate::errors::ProcessError;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The files compared when no include pattern is given
+const DEFAULT_INCLUDE: &str = "**/*.json";
+
+/// The difference between the two versions of a file
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum FileDelta {
+    Added,
+    Removed,
+    /// The deltas of the file, in path order
+    Changed {
+        deltas: Vec<Delta>,
+    },
+}
+
+/// The files that differ, by relative path. Unchanged files are left out.
+#[derive(Debug, Default, PartialEq, Clone, Serialize, Deserialize)]
+pub struct DirectoryReport {
+    pub files: BTreeMap<String, FileDelta>,
+}
+
+impl DirectoryReport {
+    /// Returns true if no file was added, removed or changed
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Returns the delta at `path` in the given file
+    pub fn delta(&self, file: &str, path: &str) -> Option<&Delta> {
+        match self.files.get(file)? {
+            FileDelta::Changed { deltas } => deltas.iter().find(|delta| delta.path == path),
+            _ => None,
+        }
+    }
+}
+
+/// Compares the JSON files of two directories
+#[derive(Debug, Clone)]
+pub struct DirectoryDiffer {
+    left: PathBuf,
+    right: PathBuf,
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl DirectoryDiffer {
+    pub fn new(left: impl AsRef<Path>, right: impl AsRef<Path>) -> Self {
+        DirectoryDiffer {
+            left: left.as_ref().to_path_buf(),
+            right: right.as_ref().to_path_buf(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+
+    /// Compares only the files matching a glob, such as `configs/**/*.json`.
+    /// Can be given several times, `**/*.json` is used when it is not.
+    pub fn include(mut self, pattern: &str) -> Self {
+        self.include.push(pattern.to_string());
+        self
+    }
+
+    /// Skips the files matching a glob, even when they are included
+    pub fn exclude(mut self, pattern: &str) -> Self {
+        self.exclude.push(pattern.to_string());
+        self
+    }
+
+    /// Compares the directories. Globs are matched against the relative
+    /// paths of the files, with `/` separators.
+    pub fn diff(&self) -> Result<DirectoryReport, ProcessError> {
+        let include = if self.include.is_empty() {
+            glob_set(&[DEFAULT_INCLUDE.to_string()])?
+        } else {
+            glob_set(&self.include)?
+        };
+        let exclude = glob_set(&self.exclude)?;
+        let selected = |file: &String| include.is_match(file) && !exclude.is_match(file);
+
+        let left: BTreeSet<String> = list_files(&self.left)?
+            .into_iter()
+            .filter(selected)
+            .collect();
+        let right: BTreeSet<String> = list_files(&self.right)?
+            .into_iter()
+            .filter(selected)
+            .collect();
+
+        let mut files = BTreeMap::new();
+        for file in left.difference(&right) {
+            files.insert(file.clone(), FileDelta::Removed);
+        }
+        for file in right.difference(&left) {
+            files.insert(file.clone(), FileDelta::Added);
+        }
+
+        let common: Vec<&String> = left.intersection(&right).collect();
+
+        #[cfg(not(feature = "parallel"))]
+        let compared = {
+            let threads = std::thread::available_parallelism().map_or(1, usize::from);
+            let chunk_size = common.len().div_ceil(threads).max(1);
+            std::thread::scope(|scope| {
+                let chunks: Vec<_> = common
+                    .chunks(chunk_size)
+                    .map(|chunk| {
+                        scope.spawn(move || {
+                            chunk
+                                .iter()
+                                .map(|file| self.compare(file))
+                                .collect::<Vec<_>>()
+                        })
+                    })
+                    .collect();
+                chunks
+                    .into_iter()
+                    .flat_map(|chunk| {
+                        chunk
+                            .join()
+                            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })?
+        };
+
+        #[cfg(feature = "parallel")]
+        let compared = {
+            use rayon::prelude::*;
+            common
+                .into_par_iter()
+                .map(|file| self.compare(file))
+                .collect::<Result<Vec<_>, _>>()?
+        };
+
+        for (file, deltas) in compared {
+            if !deltas.is_empty() {
+                files.insert(file, FileDelta::Changed { deltas });
+            }
+        }
+
+        Ok(DirectoryReport { files })
+    }
+
+    /// Returns the deltas between both versions of a file
+    fn compare(&self, file: &str) -> Result<(String, Vec<Delta>), ProcessError> {
+        let left = read_json(&self.left.join(file))?;
+        let right = read_json(&self.right.join(file))?;
+        let mut differ = Differ::new_from_json_values(left, right);
+        Ok((file.to_string(), differ.diff().get_deltas().clone()))
+    }
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet, ProcessError> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern).map_err(|error| ProcessError::InvalidPattern {
+            pattern: pattern.clone(),
+            message: error.to_string(),
+        })?;
+        builder.add(glob);
+    }
+
+    builder
+        .build()
+        .map_err(|error| ProcessError::InvalidPattern {
+            pattern: patterns.join(", "),
+            message: error.to_string(),
+        })
+}
+
+/// Returns the relative paths of the files under `root`, with `/` separators.
+/// Symbolic links are not followed.
+fn list_files(root: &Path) -> Result<Vec<String>, ProcessError> {
+    let mut files = Vec::new();
+    let mut directories = vec![root.to_path_buf()];
+
+    while let Some(directory) = directories.pop() {
+        let entries = fs::read_dir(&directory).map_err(|error| io_error(&directory, error))?;
+        for entry in entries {
+            let entry = entry.map_err(|error| io_error(&directory, error))?;
+            let path = entry.path();
+            let file_type = entry.file_type().map_err(|error| io_error(&path, error))?;
+
+            if file_type.is_dir() {
+                directories.push(path);
+            } else if file_type.is_file() {
+                let relative = path.strip_prefix(root).unwrap_or(&path);
+                let components: Vec<String> = relative
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy().into_owned())
+                    .collect();
+                files.push(components.join("/"));
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+fn read_json(path: &Path) -> Result<Value, ProcessError> {
+    let text = fs::read_to_string(path).map_err(|error| io_error(path, error))?;
+    serde_json::from_str(&text).map_err(|error| ProcessError::InvalidDocument {
+        format: "JSON".to_string(),
+        message: format!("{}: {}", path.display(), error),
+    })
+}
+
+fn io_error(path: &Path, error: std::io::Error) -> ProcessError {
+    ProcessError::Io {
+        message: format!("{}: {}", path.display(), error),
+    }
+}
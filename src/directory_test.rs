@@ -0,0 +1,122 @@
+#[cfg(test)]
+mod tests {
+    use crate::delta::Operation;
+    use crate::directory::{DirectoryDiffer, FileDelta};
+    use crate::errors::ProcessError;
+    use crate::temp_dir::TempDir;
+    use serde_json::json;
+
+    /// Writes the files to a fresh temporary directory
+    fn directory(name: &str, files: &[(&str, &str)]) -> TempDir {
+        let directory = TempDir::new(name);
+        for (file, text) in files {
+            directory.write(file, text);
+        }
+        directory
+    }
+
+    #[test]
+    fn diff_directories() {
+        let left = directory(
+            "directory-left",
+            &[
+                ("same.json", r#"{"a": 1}"#),
+                ("nested/changed.json", r#"{"a": [1], "b": true}"#),
+                ("removed.json", "[]"),
+                ("notes.txt", "not json"),
+            ],
+        );
+        let right = directory(
+            "directory-right",
+            &[
+                ("same.json", r#"{"a": 1}"#),
+                ("nested/changed.json", r#"{"a": [1, 2]}"#),
+                ("nested/added.json", "{}"),
+            ],
+        );
+
+        let report = DirectoryDiffer::new(&left, &right).diff().unwrap();
+
+        let statuses: Vec<(&str, &str)> = report
+            .files
+            .iter()
+            .map(|(file, delta)| {
+                let status = match delta {
+                    FileDelta::Added => "added",
+                    FileDelta::Removed => "removed",
+                    FileDelta::Changed { .. } => "changed",
+                };
+                (file.as_str(), status)
+            })
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("nested/added.json", "added"),
+                ("nested/changed.json", "changed"),
+                ("removed.json", "removed"),
+            ]
+        );
+
+        let delta = report.delta("nested/changed.json", "$.a[1]").unwrap();
+        assert_eq!(delta.operation, Operation::Add);
+        assert_eq!(delta.new_value, json!(2));
+        assert!(report.delta("nested/changed.json", "$.b").is_some());
+        assert!(report.delta("same.json", "$.a").is_none());
+
+        assert_eq!(
+            serde_json::to_value(&report).unwrap()["files"]["removed.json"],
+            json!({"status": "removed"})
+        );
+    }
+
+    #[test]
+    fn diff_directories_with_globs() {
+        let left = directory(
+            "globs-left",
+            &[
+                ("a/one.json", "1"),
+                ("a/skip.json", "1"),
+                ("b/two.json", "2"),
+                ("c.data", "3"),
+            ],
+        );
+        let right = directory(
+            "globs-right",
+            &[
+                ("a/one.json", "10"),
+                ("a/skip.json", "10"),
+                ("b/two.json", "20"),
+                ("c.data", "30"),
+            ],
+        );
+
+        let report = DirectoryDiffer::new(&left, &right)
+            .include("a/*.json")
+            .include("*.data")
+            .exclude("**/skip.json")
+            .diff()
+            .unwrap();
+        let files: Vec<&str> = report.files.keys().map(String::as_str).collect();
+        assert_eq!(files, vec!["a/one.json", "c.data"]);
+
+        let result = DirectoryDiffer::new(&left, &right).include("a/[").diff();
+        assert!(matches!(result, Err(ProcessError::InvalidPattern { .. })));
+    }
+
+    #[test]
+    fn diff_directories_errors() {
+        let left = directory("errors-left", &[("broken.json", "{")]);
+        let right = directory("errors-right", &[("broken.json", "{}")]);
+
+        match DirectoryDiffer::new(&left, &right).diff() {
+            Err(ProcessError::InvalidDocument { message, .. }) => {
+                assert!(message.contains("broken.json"), "{}", message)
+            }
+            other => panic!("{:?}", other),
+        }
+
+        let result = DirectoryDiffer::new(left.join("missing"), &right).diff();
+        assert!(matches!(result, Err(ProcessError::Io { .. })));
+    }
+}
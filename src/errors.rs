@@ -4,4 +4,16 @@ use derive_more::{Display, Error};
 pub enum ProcessError {
     #[display(fmt = "Unknown error: {}", message)]
     Unknown { message: String },
+    #[display(fmt = "Invalid JSON at byte {}: {}", offset, message)]
+    InvalidJson { offset: usize, message: String },
+    #[display(fmt = "IO error: {}", message)]
+    Io { message: String },
+    #[display(fmt = "Invalid path {}: {}", path, message)]
+    InvalidPath { path: String, message: String },
+    #[display(fmt = "Invalid {} document: {}", format, message)]
+    InvalidDocument { format: String, message: String },
+    #[display(fmt = "Invalid pattern {}: {}", pattern, message)]
+    InvalidPattern { pattern: String, message: String },
+    #[display(fmt = "Unknown version {}", version)]
+    UnknownVersion { version: usize },
 }
@@ -0,0 +1,326 @@
+//! Versioned history of a JSON document, stored in a file.
+//!
+//! The file is an append-only log with one JSON record per line: snapshots of
+//! the whole document, versions holding the deltas from the previous version
+//! along with their author, timestamp and message, and the settings of the
+//! history. Version 0 is the document the history was created with.
+
+use crate::delta::{invert, Delta};
+use crate::differ::Differ;
+use crate::errors::ProcessError;
+use crate::patcher::{try_patch, PatchOptions};
+use crate::transform::transform;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A version of the document, with the deltas from the previous one
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Version {
+    pub number: usize,
+    pub author: String,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub message: String,
+    pub deltas: Vec<Delta>,
+}
+
+/// A line of the history file
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Record {
+    Snapshot { number: usize, document: Value },
+    Version(Version),
+    Settings { snapshot_every: usize },
+}
+
+/// A document and its versions, see the module documentation
+#[derive(Debug)]
+pub struct History {
+    path: PathBuf,
+    snapshots: BTreeMap<usize, Value>,
+    versions: Vec<Version>,
+    document: Value,
+    snapshot_every: usize,
+    /// The `snapshot_every` last written to the log
+    logged_snapshot_every: usize,
+}
+
+impl History {
+    /// Creates the history file with `document` as version 0, fails if the
+    /// file already exists
+    pub fn create(path: impl AsRef<Path>, document: Value) -> Result<History, ProcessError> {
+        let path = path.as_ref().to_path_buf();
+        File::create_new(&path).map_err(io_error)?;
+
+        let history = History {
+            path,
+            snapshots: BTreeMap::from([(0, document.clone())]),
+            versions: Vec::new(),
+            document,
+            snapshot_every: 0,
+            logged_snapshot_every: 0,
+        };
+        history.append(&Record::Snapshot {
+            number: 0,
+            document: history.document.clone(),
+        })?;
+
+        Ok(history)
+    }
+
+    /// Reads an existing history file
+    pub fn open(path: impl AsRef<Path>) -> Result<History, ProcessError> {
+        let path = path.as_ref().to_path_buf();
+        let reader = BufReader::new(File::open(&path).map_err(io_error)?);
+
+        let mut snapshots = BTreeMap::new();
+        let mut versions = Vec::new();
+        let mut snapshot_every = 0;
+        let mut offset = 0;
+        for line in reader.lines() {
+            let line = line.map_err(io_error)?;
+            let record =
+                serde_json::from_str(&line).map_err(|error| ProcessError::InvalidJson {
+                    offset,
+                    message: error.to_string(),
+                })?;
+            offset += line.len() + 1;
+
+            match record {
+                Record::Snapshot { number, document } => {
+                    snapshots.insert(number, document);
+                }
+                Record::Version(version) => versions.push(version),
+                Record::Settings {
+                    snapshot_every: every,
+                } => snapshot_every = every,
+            }
+        }
+
+        let mut history = History {
+            path,
+            snapshots,
+            versions,
+            document: Value::Null,
+            snapshot_every,
+            logged_snapshot_every: snapshot_every,
+        };
+        history.document = history.checkout(history.head())?;
+
+        Ok(history)
+    }
+
+    /// Appends a snapshot of the document every `versions` versions, so that
+    /// `checkout` does not replay the whole log. 0, the default, never does.
+    /// The setting is written to the log with the next version, and read
+    /// back by `open`.
+    pub fn snapshot_every(mut self, versions: usize) -> Self {
+        self.snapshot_every = versions;
+        self
+    }
+
+    /// Returns the number of the latest version
+    pub fn head(&self) -> usize {
+        match self.versions.last() {
+            Some(version) => version.number,
+            None => self.oldest(),
+        }
+    }
+
+    /// Returns the document at the latest version
+    pub fn document(&self) -> &Value {
+        &self.document
+    }
+
+    /// Returns the versions after the oldest snapshot, oldest first
+    pub fn versions(&self) -> &[Version] {
+        &self.versions
+    }
+
+    /// Returns the given version, if it is still in the log
+    pub fn version(&self, number: usize) -> Option<&Version> {
+        self.versions
+            .iter()
+            .find(|version| version.number == number)
+    }
+
+    /// Records `document` as a new version and returns its number. A document
+    /// equal to the latest version does not create a version.
+    pub fn commit(
+        &mut self,
+        document: Value,
+        author: &str,
+        message: &str,
+    ) -> Result<usize, ProcessError> {
+        let mut differ = Differ::new_from_json_values(self.document.clone(), document);
+        let deltas = differ.diff().get_deltas().clone();
+        if deltas.is_empty() {
+            return Ok(self.head());
+        }
+
+        let version = Version {
+            number: self.head() + 1,
+            author: author.to_string(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_secs()),
+            message: message.to_string(),
+            deltas,
+        };
+        // the version is only logged once it is known to apply
+        let patched = try_patch(
+            self.document.clone(),
+            &version.deltas,
+            PatchOptions::default(),
+        )?;
+
+        if self.snapshot_every != self.logged_snapshot_every {
+            self.append(&Record::Settings {
+                snapshot_every: self.snapshot_every,
+            })?;
+            self.logged_snapshot_every = self.snapshot_every;
+        }
+        self.append(&Record::Version(version.clone()))?;
+
+        let number = version.number;
+        self.document = patched;
+        self.versions.push(version);
+
+        if self.snapshot_every > 0 && number.is_multiple_of(self.snapshot_every) {
+            self.snapshot()?;
+        }
+
+        Ok(number)
+    }
+
+    /// Returns the document at the given version
+    pub fn checkout(&self, number: usize) -> Result<Value, ProcessError> {
+        if number < self.oldest() || number > self.head() {
+            return Err(ProcessError::UnknownVersion { version: number });
+        }
+
+        let (start, snapshot) = self
+            .snapshots
+            .range(..=number)
+            .next_back()
+            .ok_or(ProcessError::UnknownVersion { version: number })?;
+
+        self.versions
+            .iter()
+            .filter(|version| version.number > *start && version.number <= number)
+            .try_fold(snapshot.clone(), |document, version| {
+                try_patch(document, &version.deltas, PatchOptions::default())
+            })
+    }
+
+    /// Returns the deltas from one version to another
+    pub fn diff(&self, from: usize, to: usize) -> Result<Vec<Delta>, ProcessError> {
+        let mut differ = Differ::new_from_json_values(self.checkout(from)?, self.checkout(to)?);
+        Ok(differ.diff().get_deltas().clone())
+    }
+
+    /// Undoes the changes of a version on top of the latest one, and records
+    /// the result as a new version. Changes that later versions made to the
+    /// same values are kept.
+    pub fn revert(&mut self, number: usize, author: &str) -> Result<usize, ProcessError> {
+        let version = self
+            .version(number)
+            .ok_or(ProcessError::UnknownVersion { version: number })?;
+        let undo = invert(&version.deltas);
+
+        // the undo is rebased on the versions that came after
+        let later = self.diff(number, self.head())?;
+        let undo = transform(&later, &undo).b;
+
+        let document = try_patch(self.document.clone(), &undo, PatchOptions::default())?;
+        self.commit(document, author, &format!("Revert version {}", number))
+    }
+
+    /// Appends a snapshot of the latest version to the log
+    pub fn snapshot(&mut self) -> Result<(), ProcessError> {
+        let number = self.head();
+        self.append(&Record::Snapshot {
+            number,
+            document: self.document.clone(),
+        })?;
+        self.snapshots.insert(number, self.document.clone());
+        Ok(())
+    }
+
+    /// Rewrites the log to start with a snapshot of the given version. The
+    /// versions before it can no longer be checked out.
+    pub fn compact(&mut self, number: usize) -> Result<(), ProcessError> {
+        let document = self.checkout(number)?;
+
+        self.snapshots.retain(|snapshot, _| *snapshot > number);
+        self.snapshots.insert(number, document);
+        self.versions.retain(|version| version.number > number);
+
+        let mut records: Vec<(usize, Record)> = self
+            .snapshots
+            .iter()
+            .map(|(number, document)| {
+                let record = Record::Snapshot {
+                    number: *number,
+                    document: document.clone(),
+                };
+                (*number, record)
+            })
+            .chain(
+                self.versions
+                    .iter()
+                    .map(|version| (version.number, Record::Version(version.clone()))),
+            )
+            .collect();
+        // a snapshot comes after the version it was taken at
+        records
+            .sort_by_key(|(number, record)| (*number, matches!(record, Record::Snapshot { .. })));
+
+        let mut text = to_line(&Record::Settings {
+            snapshot_every: self.snapshot_every,
+        })?;
+        for (_, record) in &records {
+            text.push_str(&to_line(record)?);
+        }
+
+        // the new log replaces the old one at once
+        let temporary = self.path.with_extension("compact");
+        fs::write(&temporary, text).map_err(io_error)?;
+        fs::rename(&temporary, &self.path).map_err(io_error)?;
+        self.logged_snapshot_every = self.snapshot_every;
+        Ok(())
+    }
+
+    /// Returns the number of the oldest version that can be checked out
+    fn oldest(&self) -> usize {
+        self.snapshots.keys().next().copied().unwrap_or_default()
+    }
+
+    fn append(&self, record: &Record) -> Result<(), ProcessError> {
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(&self.path)
+            .map_err(io_error)?;
+        file.write_all(to_line(record)?.as_bytes())
+            .map_err(io_error)
+    }
+}
+
+fn to_line(record: &Record) -> Result<String, ProcessError> {
+    let mut line = serde_json::to_string(record).map_err(|error| ProcessError::Unknown {
+        message: error.to_string(),
+    })?;
+    line.push('\n');
+    Ok(line)
+}
+
+fn io_error(error: std::io::Error) -> ProcessError {
+    ProcessError::Io {
+        message: error.to_string(),
+    }
+}
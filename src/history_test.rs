@@ -0,0 +1,107 @@
+#[cfg(test)]
+mod tests {
+    use crate::errors::ProcessError;
+    use crate::history::History;
+    use crate::temp_dir::TempDir;
+    use serde_json::json;
+    use std::fs;
+
+    #[test]
+    fn history_commit_and_checkout() {
+        let directory = TempDir::new("history-checkout");
+        let path = directory.join("history.ndjson");
+        let mut history = History::create(&path, json!({"a": 1})).unwrap();
+
+        assert_eq!(
+            history.commit(json!({"a": 2}), "ann", "change a").unwrap(),
+            1
+        );
+        assert_eq!(
+            history.commit(json!({"a": 2}), "ann", "no change").unwrap(),
+            1
+        );
+        assert_eq!(
+            history
+                .commit(json!({"a": 2, "b": [1]}), "bob", "add b")
+                .unwrap(),
+            2
+        );
+
+        assert_eq!(history.checkout(0).unwrap(), json!({"a": 1}));
+        assert_eq!(history.checkout(1).unwrap(), json!({"a": 2}));
+        assert_eq!(history.diff(0, 2).unwrap().len(), 2);
+        assert!(matches!(
+            history.checkout(3),
+            Err(ProcessError::UnknownVersion { version: 3 })
+        ));
+
+        let version = history.version(2).unwrap();
+        assert_eq!(
+            (version.author.as_str(), version.message.as_str()),
+            ("bob", "add b")
+        );
+
+        let reopened = History::open(&path).unwrap();
+        assert_eq!(reopened.head(), 2);
+        assert_eq!(reopened.document(), &json!({"a": 2, "b": [1]}));
+        assert_eq!(reopened.versions(), history.versions());
+        assert!(History::create(&path, json!({})).is_err());
+    }
+
+    #[test]
+    fn history_revert() {
+        let directory = TempDir::new("history-revert");
+        let path = directory.join("history.ndjson");
+        let mut history = History::create(&path, json!({"a": 1, "list": [1]})).unwrap();
+
+        history
+            .commit(json!({"a": 2, "list": [1, 2]}), "ann", "")
+            .unwrap();
+        history
+            .commit(json!({"a": 3, "list": [1, 2, 3]}), "bob", "")
+            .unwrap();
+
+        let number = history.revert(1, "ann").unwrap();
+
+        assert_eq!(number, 3);
+        assert_eq!(history.document(), &json!({"a": 3, "list": [1, 3]}));
+        assert_eq!(history.version(3).unwrap().message, "Revert version 1");
+    }
+
+    #[test]
+    fn history_snapshots_and_compaction() {
+        let directory = TempDir::new("history-compact");
+        let path = directory.join("history.ndjson");
+        let mut history = History::create(&path, json!({"n": 0}))
+            .unwrap()
+            .snapshot_every(2);
+
+        for n in 1..=5 {
+            history.commit(json!({"n": n}), "ann", "").unwrap();
+        }
+        history.compact(3).unwrap();
+
+        let reopened = History::open(&path).unwrap();
+        assert_eq!(reopened.head(), 5);
+        assert_eq!(reopened.checkout(3).unwrap(), json!({"n": 3}));
+        assert_eq!(reopened.checkout(5).unwrap(), json!({"n": 5}));
+        assert!(reopened.checkout(2).is_err());
+        assert_eq!(reopened.versions().len(), 2);
+    }
+
+    #[test]
+    fn history_keeps_snapshot_setting() {
+        let directory = TempDir::new("history-settings");
+        let path = directory.join("history.ndjson");
+        let mut history = History::create(&path, json!({"n": 0}))
+            .unwrap()
+            .snapshot_every(2);
+        history.commit(json!({"n": 1}), "ann", "").unwrap();
+
+        let mut reopened = History::open(&path).unwrap();
+        reopened.commit(json!({"n": 2}), "ann", "").unwrap();
+
+        let log = fs::read_to_string(&path).unwrap();
+        assert!(log.contains(r#"{"type":"snapshot","number":2,"document":{"n":2}}"#));
+    }
+}
@@ -0,0 +1,19 @@
+//! JSON5 and JSONC documents, behind the `json5` feature.
+//!
+//! Comments, trailing commas, unquoted keys, single quoted strings and the
+//! other JSON5 extensions are accepted, so configuration files such as
+//! `tsconfig.json` or VS Code settings can be compared with `Differ`. Numbers
+//! that JSON cannot represent, such as `Infinity` or `NaN`, become nulls.
+//!
+//! `patcher::patch_text` writes JSONC documents back with their comments.
+
+use crate::errors::ProcessError;
+use serde_json::Value;
+
+/// Parses a JSON5 or JSONC document into a `Value`
+pub fn from_str(text: &str) -> Result<Value, ProcessError> {
+    ::json5::from_str(text).map_err(|error| ProcessError::InvalidDocument {
+        format: "JSON5".to_string(),
+        message: error.to_string(),
+    })
+}
@@ -0,0 +1,43 @@
+#[cfg(test)]
+mod tests {
+    use crate::delta::Operation;
+    use crate::differ::Differ;
+    use crate::json5;
+    use serde_json::json;
+
+    const SETTINGS: &str = r#"{
+  // editor settings
+  "editor.tabSize": 2,
+  files: {
+    exclude: ['node_modules', 'target',],
+  },
+  /* hex and trailing commas */
+  mask: 0xff,
+}"#;
+
+    #[test]
+    fn json5_from_str() {
+        assert_eq!(
+            json5::from_str(SETTINGS).unwrap(),
+            json!({
+                "editor.tabSize": 2,
+                "files": {"exclude": ["node_modules", "target"]},
+                "mask": 255
+            })
+        );
+        assert_eq!(json5::from_str("[Infinity, NaN]").unwrap(), json!([null, null]));
+        assert!(json5::from_str("{a: }").is_err());
+    }
+
+    #[test]
+    fn json5_diff() {
+        let changed = SETTINGS.replace("'target',", "'target', 'dist'");
+
+        let mut differ = Differ::new_from_json5(SETTINGS, &changed).unwrap();
+
+        assert!(differ
+            .diff()
+            .has_path_changed("$.files.exclude[2]", Operation::Add));
+        assert_eq!(differ.get_deltas().len(), 1);
+    }
+}
@@ -1,9 +1,54 @@
+#[cfg(feature = "cbor")]
+pub mod cbor;
+#[cfg(feature = "cbor")]
+mod cbor_test;
+pub mod compose;
+mod compose_test;
 pub mod delta;
 pub mod differ;
 mod differ_test;
-mod errors;
+pub mod directory;
+mod directory_test;
+pub mod errors;
+pub mod history;
+mod history_test;
+#[cfg(feature = "json5")]
+pub mod json5;
+#[cfg(feature = "json5")]
+mod json5_test;
 mod lib_test;
+pub mod merge;
+mod merge_test;
+#[cfg(feature = "msgpack")]
+pub mod msgpack;
+#[cfg(feature = "msgpack")]
+mod msgpack_test;
+pub mod ndjson;
+mod ndjson_test;
+pub mod openapi;
+mod openapi_test;
 pub mod patcher;
 mod patcher_test;
+mod properties_test;
+pub mod schema;
+mod schema_test;
+#[cfg(test)]
+mod strategies;
+pub mod stream;
+mod stream_test;
+#[cfg(test)]
+mod temp_dir;
+mod text;
+mod text_test;
+#[cfg(feature = "toml")]
+pub mod toml;
+#[cfg(feature = "toml")]
+mod toml_test;
+pub mod transform;
+mod transform_test;
 mod utils;
 mod utils_test;
+#[cfg(feature = "yaml")]
+pub mod yaml;
+#[cfg(feature = "yaml")]
+mod yaml_test;
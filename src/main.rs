@@ -0,0 +1,287 @@
+//! Command line interface, to be used as a git merge driver and difftool for
+//! JSON files.
+
+use json_differ::delta::{Delta, Operation};
+use json_differ::differ::Differ;
+use json_differ::directory::{DirectoryDiffer, DirectoryReport, FileDelta};
+use json_differ::merge::{merge, Strategy};
+use json_differ::openapi;
+use json_differ::patcher::{parse_text, try_patch_text};
+use serde_json::Value;
+use std::fmt::Write as _;
+use std::fs;
+use std::process::ExitCode;
+
+mod main_test;
+#[cfg(test)]
+mod temp_dir;
+
+const USAGE: &str = "Usage:
+  json-differ merge [--strategy ours|theirs] <base> <ours> <theirs>
+      Three-way merge, the result is written to <ours> keeping its
+      formatting. Exits with 1 and leaves <ours> untouched when conflicts
+      are left, see `git config merge.json.driver`.
+  json-differ difftool <left> <right>
+      Prints the structural differences between two files. Also accepts the
+      7 arguments git passes to GIT_EXTERNAL_DIFF.
+  json-differ dir [--include <glob>]... [--exclude <glob>]... [--json] <left> <right>
+      Compares the JSON files of two directories, paired by relative path.
+      Only `**/*.json` files are compared when no --include is given.
+      --json prints the report as JSON.
+  json-differ openapi <old> <new>
+      Prints the changelog between two OpenAPI 3 documents. Exits with 1
+      when a change breaks the API consumers.";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match run(&args) {
+        Ok(code) => code,
+        Err(message) => {
+            eprintln!("json-differ: {}", message);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<ExitCode, String> {
+    match args.split_first() {
+        Some((command, rest)) if command == "merge" => merge_command(rest),
+        Some((command, rest)) if command == "difftool" => difftool_command(rest),
+        Some((command, rest)) if command == "dir" => dir_command(rest),
+        Some((command, rest)) if command == "openapi" => openapi_command(rest),
+        Some((command, _)) if command == "-h" || command == "--help" => {
+            println!("{}", USAGE);
+            Ok(ExitCode::SUCCESS)
+        }
+        _ => Err(format!("expected a command\n\n{}", USAGE)),
+    }
+}
+
+/// `merge [--strategy ours|theirs] <base> <ours> <theirs>`
+fn merge_command(args: &[String]) -> Result<ExitCode, String> {
+    let mut strategy = None;
+    let mut files = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--strategy" => {
+                strategy = match args.next().map(String::as_str) {
+                    Some("ours") => Some(Strategy::Ours),
+                    Some("theirs") => Some(Strategy::Theirs),
+                    other => return Err(format!("unknown strategy {:?}", other)),
+                }
+            }
+            _ => files.push(arg),
+        }
+    }
+
+    let [base, ours, theirs] = files[..] else {
+        return Err(format!("merge expects 3 files\n\n{}", USAGE));
+    };
+
+    let ours_text = read_text(ours)?;
+    let ours_value = parse(ours, &ours_text)?;
+    let result = merge(&read_json(base)?, &ours_value, &read_json(theirs)?);
+
+    // without a strategy, conflicts leave our file untouched for git to report
+    if let (None, false) = (strategy, result.is_clean()) {
+        for conflict in result.conflicts() {
+            eprintln!(
+                "conflict at {}: base {}, ours {}, theirs {}",
+                conflict.path,
+                display(conflict.base.as_ref()),
+                display(conflict.ours.as_ref()),
+                display(conflict.theirs.as_ref())
+            );
+        }
+        return Ok(ExitCode::FAILURE);
+    }
+
+    let merged = result.resolve(strategy.unwrap_or(Strategy::Ours));
+    write_json(ours, &ours_text, ours_value, &merged)?;
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// `difftool <left> <right>`, or the 7 arguments of `GIT_EXTERNAL_DIFF`:
+/// `path old-file old-hex old-mode new-file new-hex new-mode`
+fn difftool_command(args: &[String]) -> Result<ExitCode, String> {
+    let (left, right) = match args {
+        [left, right] => (left, right),
+        [_, left, _, _, right, _, _] => (left, right),
+        _ => return Err(format!("difftool expects 2 files\n\n{}", USAGE)),
+    };
+
+    let mut differ = Differ::new_from_json_values(read_json(left)?, read_json(right)?);
+    print!("{}", format_diff(left, right, differ.diff().get_deltas()));
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// `dir [--include <glob>]... [--exclude <glob>]... [--json] <left> <right>`
+fn dir_command(args: &[String]) -> Result<ExitCode, String> {
+    let mut include = Vec::new();
+    let mut exclude = Vec::new();
+    let mut json = false;
+    let mut directories = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--include" | "--exclude" => {
+                let pattern = args
+                    .next()
+                    .ok_or_else(|| format!("{} expects a glob", arg))?;
+                match arg.as_str() {
+                    "--include" => include.push(pattern),
+                    _ => exclude.push(pattern),
+                }
+            }
+            "--json" => json = true,
+            _ => directories.push(arg),
+        }
+    }
+
+    let [left, right] = directories[..] else {
+        return Err(format!("dir expects 2 directories\n\n{}", USAGE));
+    };
+
+    let mut differ = DirectoryDiffer::new(left, right);
+    for pattern in include {
+        differ = differ.include(pattern);
+    }
+    for pattern in exclude {
+        differ = differ.exclude(pattern);
+    }
+    let report = differ.diff().map_err(|error| error.to_string())?;
+
+    if json {
+        let text = serde_json::to_string_pretty(&report).map_err(|error| error.to_string())?;
+        println!("{}", text);
+    } else {
+        print!("{}", format_report(left, right, &report));
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// `openapi <old> <new>`
+fn openapi_command(args: &[String]) -> Result<ExitCode, String> {
+    let [old, new] = args else {
+        return Err(format!("openapi expects 2 files\n\n{}", USAGE));
+    };
+
+    let changelog =
+        openapi::diff(&read_json(old)?, &read_json(new)?).map_err(|error| error.to_string())?;
+    print!("{}", changelog);
+
+    if changelog.is_breaking() {
+        return Ok(ExitCode::FAILURE);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Formats the files added and removed, then the deltas of the files changed
+fn format_report(left: &str, right: &str, report: &DirectoryReport) -> String {
+    let mut output = String::new();
+
+    for (file, delta) in &report.files {
+        let _ = match delta {
+            FileDelta::Added => writeln!(output, "Only in {}: {}", right, file),
+            FileDelta::Removed => writeln!(output, "Only in {}: {}", left, file),
+            FileDelta::Changed { .. } => Ok(()),
+        };
+    }
+    for (file, delta) in &report.files {
+        if let FileDelta::Changed { deltas } = delta {
+            let (left, right) = (format!("{}/{}", left, file), format!("{}/{}", right, file));
+            output.push_str(&format_diff(&left, &right, deltas));
+        }
+    }
+
+    output
+}
+
+/// Formats the deltas one per line, prefixed with `+`, `-` or `~`
+fn format_diff(left: &str, right: &str, deltas: &[Delta]) -> String {
+    let mut output = String::new();
+    if deltas.is_empty() {
+        return output;
+    }
+
+    let _ = writeln!(output, "--- {}\n+++ {}", left, right);
+    for delta in deltas {
+        let _ = match delta.operation {
+            Operation::Add => writeln!(output, "+ {}: {}", delta.path, delta.new_value),
+            Operation::Delete => writeln!(output, "- {}: {}", delta.path, delta.old_value),
+            Operation::Change => writeln!(
+                output,
+                "~ {}: {} -> {}",
+                delta.path, delta.old_value, delta.new_value
+            ),
+        };
+    }
+
+    output
+}
+
+fn display(value: Option<&Value>) -> String {
+    value.map_or_else(|| "(missing)".to_string(), Value::to_string)
+}
+
+fn read_json(path: &str) -> Result<Value, String> {
+    parse(path, &read_text(path)?)
+}
+
+fn read_text(path: &str) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))
+}
+
+/// Parses a file, allowing comments and trailing commas, an empty one such as
+/// the `/dev/null` git passes for added and deleted files reads as `null`
+fn parse(path: &str, text: &str) -> Result<Value, String> {
+    if text.trim().is_empty() {
+        return Ok(Value::Null);
+    }
+
+    #[cfg(feature = "yaml")]
+    if is_yaml(path) {
+        return json_differ::yaml::from_str(text).map_err(|error| format!("{}: {}", path, error));
+    }
+
+    parse_text(text).map_err(|error| format!("{}: {}", path, error))
+}
+
+/// Writes `value` over the file holding `original`, patching its text so the
+/// formatting of the parts left unchanged is kept
+fn write_json(path: &str, text: &str, original: Value, value: &Value) -> Result<(), String> {
+    let deltas = Differ::new_from_json_values(original, value.clone())
+        .diff()
+        .get_deltas()
+        .clone();
+
+    let patched = if text.trim().is_empty() {
+        let mut text = serde_json::to_string_pretty(value).map_err(|error| error.to_string())?;
+        text.push('\n');
+        Ok(text)
+    } else {
+        #[cfg(feature = "yaml")]
+        if is_yaml(path) {
+            json_differ::yaml::patch(text, &deltas)
+        } else {
+            try_patch_text(text, &deltas)
+        }
+        #[cfg(not(feature = "yaml"))]
+        try_patch_text(text, &deltas)
+    };
+
+    let patched = patched.map_err(|error| format!("{}: {}", path, error))?;
+    fs::write(path, patched).map_err(|error| format!("{}: {}", path, error))
+}
+
+#[cfg(feature = "yaml")]
+fn is_yaml(path: &str) -> bool {
+    path.ends_with(".yaml") || path.ends_with(".yml")
+}
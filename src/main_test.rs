@@ -0,0 +1,224 @@
+#[cfg(test)]
+mod tests {
+    use crate::temp_dir::TempDir;
+    use crate::{format_diff, format_report, run};
+    use json_differ::differ::Differ;
+    use json_differ::directory::DirectoryDiffer;
+    use serde_json::{json, Value};
+    use std::fs;
+    use std::ops::Deref;
+    use std::process::ExitCode;
+
+    /// Files in a temporary directory, removed when dropped
+    struct Files {
+        directory: TempDir,
+        paths: Vec<String>,
+    }
+
+    impl Deref for Files {
+        type Target = [String];
+
+        fn deref(&self) -> &[String] {
+            &self.paths
+        }
+    }
+
+    /// Writes the documents to a fresh temporary directory
+    fn files(name: &str, documents: &[Value]) -> Files {
+        let texts: Vec<String> = documents.iter().map(Value::to_string).collect();
+        text_files(name, &texts)
+    }
+
+    /// Writes the texts to a fresh temporary directory, as JSON files
+    fn text_files(name: &str, texts: &[String]) -> Files {
+        let directory = TempDir::new(name);
+        let paths = texts
+            .iter()
+            .enumerate()
+            .map(|(index, text)| {
+                let path = directory.write(&format!("{}.json", index), text);
+                path.to_string_lossy().into_owned()
+            })
+            .collect();
+
+        Files { directory, paths }
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn read(path: &str) -> Value {
+        serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn merge_driver() {
+        let paths = files(
+            "merge",
+            &[
+                json!({"a": 1, "b": 1}),
+                json!({"a": 2, "b": 1}),
+                json!({"a": 1, "b": 2}),
+            ],
+        );
+
+        let code = run(&args(&["merge", &paths[0], &paths[1], &paths[2]])).unwrap();
+
+        assert_eq!(code, ExitCode::SUCCESS);
+        assert_eq!(read(&paths[1]), json!({"a": 2, "b": 2}));
+    }
+
+    #[test]
+    fn merge_driver_conflicts() {
+        let documents = [json!({"a": 1}), json!({"a": 2}), json!({"a": 3})];
+
+        let texts = [
+            "{\"a\": 1, \"b\": 1}",
+            "{\"a\": 2, \"b\": 1}",
+            "{\"a\": 3, \"b\": 2}",
+        ]
+        .map(String::from);
+        let paths = text_files("conflicts", &texts);
+        let code = run(&args(&["merge", &paths[0], &paths[1], &paths[2]])).unwrap();
+        assert_eq!(code, ExitCode::FAILURE);
+        assert_eq!(fs::read_to_string(&paths[1]).unwrap(), texts[1]);
+
+        let paths = files("strategy", &documents);
+        let code = run(&args(&[
+            "merge",
+            "--strategy",
+            "theirs",
+            &paths[0],
+            &paths[1],
+            &paths[2],
+        ]))
+        .unwrap();
+        assert_eq!(code, ExitCode::SUCCESS);
+        assert_eq!(read(&paths[1]), json!({"a": 3}));
+    }
+
+    #[test]
+    fn merge_driver_keeps_formatting() {
+        let texts = [
+            "{\n  \"a\": 1,\n  \"b\": 1\n}\n",
+            "{\n    \"a\": 2.50,\n    \"b\": 1\n}",
+            "{\"a\": 1, \"b\": 2, \"c\": []}",
+        ]
+        .map(String::from);
+        let paths = text_files("formatting", &texts);
+
+        let code = run(&args(&["merge", &paths[0], &paths[1], &paths[2]])).unwrap();
+
+        assert_eq!(code, ExitCode::SUCCESS);
+        assert_eq!(
+            fs::read_to_string(&paths[1]).unwrap(),
+            "{\n    \"a\": 2.50,\n    \"b\": 2,\n    \"c\": []\n}"
+        );
+    }
+
+    #[test]
+    fn merge_driver_reads_comments() {
+        let texts = [
+            "{\"a\": 1, \"b\": 1}",
+            "{\n  // answer\n  \"a\": 2,\n  \"b\": 1,\n}\n",
+            "{\"a\": 1, \"b\": 2}",
+        ]
+        .map(String::from);
+        let paths = text_files("comments", &texts);
+
+        let code = run(&args(&["merge", &paths[0], &paths[1], &paths[2]])).unwrap();
+
+        assert_eq!(code, ExitCode::SUCCESS);
+        assert_eq!(
+            fs::read_to_string(&paths[1]).unwrap(),
+            "{\n  // answer\n  \"a\": 2,\n  \"b\": 2,\n}\n"
+        );
+    }
+
+    #[test]
+    fn difftool_added_and_deleted_files() {
+        let paths = files("dev-null", &[json!({"a": 1})]);
+
+        for (left, right) in [("/dev/null", &paths[0][..]), (&paths[0][..], "/dev/null")] {
+            let code = run(&args(&["difftool", left, right])).unwrap();
+            assert_eq!(code, ExitCode::SUCCESS);
+        }
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        assert!(run(&args(&[])).is_err());
+        assert!(run(&args(&["merge", "a.json"])).is_err());
+        assert!(run(&args(&["merge", "--strategy", "mine", "a", "b", "c"])).is_err());
+        assert!(run(&args(&["difftool", "missing.json", "missing.json"])).is_err());
+    }
+
+    #[test]
+    fn difftool_format() {
+        let mut differ =
+            Differ::new_from_json_values(json!({"a": 1, "b": "x"}), json!({"a": 2, "c": [true]}));
+
+        assert_eq!(
+            format_diff("left.json", "right.json", differ.diff().get_deltas()),
+            "--- left.json\n+++ right.json\n~ $.a: 1 -> 2\n- $.b: \"x\"\n+ $.c: [true]\n"
+        );
+        assert_eq!(format_diff("left.json", "right.json", &[]), "");
+    }
+
+    #[test]
+    fn dir_report() {
+        let left = files("dir-left", &[json!({"a": 1}), json!([1])]);
+        let right = files("dir-right", &[json!({"a": 2})]);
+        let (left_directory, right_directory) = (&left.directory, &right.directory);
+
+        let report = DirectoryDiffer::new(left_directory, right_directory)
+            .diff()
+            .unwrap();
+        assert_eq!(
+            format_report("left", "right", &report),
+            "Only in left: 1.json\n--- left/0.json\n+++ right/0.json\n~ $.a: 1 -> 2\n"
+        );
+
+        let (left, right) = (
+            left_directory.to_string_lossy(),
+            right_directory.to_string_lossy(),
+        );
+        let code = run(&args(&[
+            "dir",
+            "--exclude",
+            "1.json",
+            "--json",
+            &left,
+            &right,
+        ]))
+        .unwrap();
+        assert_eq!(code, ExitCode::SUCCESS);
+        assert!(run(&args(&["dir", &left])).is_err());
+        assert!(run(&args(&["dir", "--include"])).is_err());
+    }
+
+    #[test]
+    fn openapi_changelog() {
+        let api = |parameters: Value| {
+            json!({
+                "openapi": "3.0.0",
+                "paths": {"/items": {"get": {"parameters": parameters}}}
+            })
+        };
+        let paths = files(
+            "openapi",
+            &[
+                api(json!([])),
+                api(json!([{"name": "q", "in": "query"}])),
+                api(json!([{"name": "q", "in": "query", "required": true}])),
+            ],
+        );
+
+        let code = run(&args(&["openapi", &paths[0], &paths[1]])).unwrap();
+        assert_eq!(code, ExitCode::SUCCESS);
+        let code = run(&args(&["openapi", &paths[1], &paths[2]])).unwrap();
+        assert_eq!(code, ExitCode::FAILURE);
+        assert!(run(&args(&["openapi", &paths[0]])).is_err());
+    }
+}
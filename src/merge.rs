@@ -0,0 +1,250 @@
+//! Three-way merge of JSON documents.
+//!
+//! `merge` diffs a common base against two edited versions, ours and theirs,
+//! and combines both sets of deltas. Deltas touching different paths are
+//! applied together, while the places both sides changed differently are
+//! reported as conflicts, to be resolved with a `ConflictResolver`.
+
+use crate::delta::{compare_paths, Delta, Operation};
+use crate::differ::Differ;
+use crate::patcher::{patch, PatchOptions};
+use crate::transform::split_index;
+use crate::utils::{get, is_path_prefix, path_prefixes, push_index, segments, Segment};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+/// A place both sides changed differently, values are `None` where absent
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Conflict {
+    pub path: String,
+    pub base: Option<Value>,
+    pub ours: Option<Value>,
+    pub theirs: Option<Value>,
+}
+
+/// Picks the value of a conflicting path, see `MergeResult::resolve`
+pub trait ConflictResolver {
+    /// Returns the merged value at `conflict.path`, `None` to remove it
+    fn resolve(&mut self, conflict: &Conflict) -> Option<Value>;
+}
+
+impl<F> ConflictResolver for F
+where
+    F: FnMut(&Conflict) -> Option<Value>,
+{
+    fn resolve(&mut self, conflict: &Conflict) -> Option<Value> {
+        self(conflict)
+    }
+}
+
+/// Resolves every conflict in favor of one side
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum Strategy {
+    Ours,
+    Theirs,
+}
+
+impl ConflictResolver for Strategy {
+    fn resolve(&mut self, conflict: &Conflict) -> Option<Value> {
+        match self {
+            Strategy::Ours => conflict.ours.clone(),
+            Strategy::Theirs => conflict.theirs.clone(),
+        }
+    }
+}
+
+/// Outcome of `merge`: the deltas both sides agree on and the conflicts
+#[derive(Debug, PartialEq, Clone)]
+pub struct MergeResult {
+    base: Value,
+    deltas: Vec<Delta>,
+    conflicts: Vec<Conflict>,
+}
+
+impl MergeResult {
+    /// Returns true if the two sides did not conflict
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+
+    /// Returns the conflicts, sorted by path
+    pub fn conflicts(&self) -> &[Conflict] {
+        &self.conflicts
+    }
+
+    /// Returns the merged document if there is no conflict
+    pub fn merged(&self) -> Option<Value> {
+        self.is_clean().then(|| self.resolve(Strategy::Ours))
+    }
+
+    /// Returns the merged document, with the conflicting paths set to the
+    /// value picked by the resolver
+    pub fn resolve<R: ConflictResolver>(&self, mut resolver: R) -> Value {
+        let mut deltas = self.deltas.clone();
+
+        for conflict in &self.conflicts {
+            let resolved = resolver.resolve(conflict);
+            let path = conflict.path.clone();
+            let delta = match (&conflict.base, resolved) {
+                (None, None) => continue,
+                (None, Some(value)) => Delta::new(Operation::Add, path, Value::Null, value),
+                (Some(base), None) => {
+                    Delta::new(Operation::Delete, path, base.clone(), Value::Null)
+                }
+                (Some(base), Some(value)) if *base == value => continue,
+                (Some(base), Some(value)) => {
+                    Delta::new(Operation::Change, path, base.clone(), value)
+                }
+            };
+            deltas.push(delta);
+        }
+
+        deltas.sort_by(|a, b| compare_paths(&a.path, &b.path));
+        patch(
+            self.base.clone(),
+            &reindexed(deltas),
+            PatchOptions::default(),
+        )
+    }
+}
+
+/// Merges the changes made from `base` to `ours` and from `base` to `theirs`.
+///
+/// Two deltas overlap when they have the same path or one path is nested in
+/// the other, additions counting from the first level they create.
+/// Overlapping deltas leading to the same value are merged, the other ones
+/// become a conflict on the shortest of their paths. As indices are only
+/// stable within arrays keeping their length, a conflict inside an array
+/// that grows or shrinks on either side covers the whole array.
+pub fn merge(base: &Value, ours: &Value, theirs: &Value) -> MergeResult {
+    let ours_deltas = diff(base, ours);
+    let theirs_deltas = diff(base, theirs);
+
+    let theirs_anchors: HashSet<&str> = theirs_deltas
+        .iter()
+        .map(|delta| anchor(delta, base))
+        .collect();
+    let theirs_prefixes: HashSet<&str> = theirs_anchors
+        .iter()
+        .flat_map(|anchor| path_prefixes(anchor))
+        .collect();
+
+    // the shortest path of every group of overlapping deltas
+    let mut regions: Vec<&str> = ours_deltas
+        .iter()
+        .map(|delta| anchor(delta, base))
+        .filter_map(|anchor| {
+            path_prefixes(anchor)
+                .find(|prefix| theirs_anchors.contains(prefix))
+                .or_else(|| theirs_prefixes.contains(anchor).then_some(anchor))
+        })
+        .map(|region| stable_region(region, base, ours, theirs))
+        .collect();
+
+    regions.sort_by(|a, b| compare_paths(a, b));
+    regions.dedup_by(|path, region| is_path_prefix(region, path));
+
+    let region_set: HashSet<&str> = regions.iter().copied().collect();
+    let in_region = |delta: &Delta| path_prefixes(&delta.path).any(|p| region_set.contains(p));
+
+    let mut deltas: Vec<Delta> = ours_deltas
+        .iter()
+        .chain(theirs_deltas.iter())
+        .filter(|delta| !in_region(delta))
+        .cloned()
+        .collect();
+
+    let mut conflicts = Vec::new();
+    for region in regions {
+        let value = |document| get(document, segments(region)).cloned();
+        let (ours_value, theirs_value) = (value(ours), value(theirs));
+
+        if ours_value == theirs_value {
+            deltas.extend(
+                ours_deltas
+                    .iter()
+                    .filter(|delta| is_path_prefix(region, &delta.path))
+                    .cloned(),
+            );
+            continue;
+        }
+
+        conflicts.push(Conflict {
+            path: region.to_string(),
+            base: value(base),
+            ours: ours_value,
+            theirs: theirs_value,
+        });
+    }
+
+    MergeResult {
+        base: base.clone(),
+        deltas,
+        conflicts,
+    }
+}
+
+/// Moves the elements one side appends to an array before the elements the
+/// other side removes from its end, as `patch` inserts elements once the
+/// removed ones are gone
+fn reindexed(deltas: Vec<Delta>) -> Vec<Delta> {
+    let mut removed: HashMap<String, usize> = HashMap::new();
+    for delta in &deltas {
+        if let (Operation::Delete, Some((array, _))) = (&delta.operation, split_index(&delta.path))
+        {
+            *removed.entry(array.to_string()).or_default() += 1;
+        }
+    }
+
+    deltas
+        .into_iter()
+        .map(|delta| match (&delta.operation, split_index(&delta.path)) {
+            (Operation::Add, Some((array, index))) if removed.contains_key(array) => {
+                let mut path = array.to_string();
+                push_index(&mut path, index.saturating_sub(removed[array]));
+                Delta::new(Operation::Add, path, Value::Null, delta.new_value)
+            }
+            _ => delta,
+        })
+        .collect()
+}
+
+fn diff(left: &Value, right: &Value) -> Vec<Delta> {
+    let mut differ = Differ::new_from_json_values(left.clone(), right.clone());
+    differ.diff().get_deltas().clone()
+}
+
+/// Returns the path a delta takes effect at: the outermost level missing in
+/// `base` for an addition, as two additions to a missing object or array
+/// both create it, and the delta path otherwise
+fn anchor<'a>(delta: &'a Delta, base: &Value) -> &'a str {
+    match delta.operation {
+        Operation::Add => path_prefixes(&delta.path)
+            .find(|prefix| get(base, segments(prefix)).is_none())
+            .unwrap_or(&delta.path),
+        _ => &delta.path,
+    }
+}
+
+/// Returns the outermost array, along the path, that does not have the same
+/// length in the three documents, or the path itself if there is none
+fn stable_region<'a>(path: &'a str, base: &Value, ours: &Value, theirs: &Value) -> &'a str {
+    let length = |document, prefix| match get(document, segments(prefix)) {
+        Some(Value::Array(array)) => Some(array.len()),
+        _ => None,
+    };
+
+    path_prefixes(path)
+        .filter(|prefix| {
+            matches!(
+                segments(&path[prefix.len()..]).next(),
+                Some(Segment::Index(_))
+            )
+        })
+        .find(|prefix| {
+            let base_length = length(base, prefix);
+            base_length != length(ours, prefix) || base_length != length(theirs, prefix)
+        })
+        .unwrap_or(path)
+}
@@ -0,0 +1,162 @@
+#[cfg(test)]
+mod tests {
+    use crate::merge::{merge, Conflict, Strategy};
+    use serde_json::{json, Value};
+
+    #[test]
+    fn merge_without_conflicts() {
+        let base = json!({"name": "a", "tags": ["x"], "size": 1, "meta": {"v": 1}});
+        let ours = json!({"name": "b", "tags": ["x"], "size": 1, "meta": {"v": 1}});
+        let theirs = json!({"name": "a", "tags": ["x", "y"], "meta": {"v": 1, "w": 2}});
+
+        let result = merge(&base, &ours, &theirs);
+
+        assert!(result.is_clean());
+        assert_eq!(
+            result.merged(),
+            Some(json!({"name": "b", "tags": ["x", "y"], "meta": {"v": 1, "w": 2}}))
+        );
+    }
+
+    #[test]
+    fn merge_same_changes() {
+        let base = json!({"a": 1, "b": [1, 2, 3]});
+        let ours = json!({"a": 2, "b": [1, 2]});
+        let theirs = json!({"a": 2, "b": [1, 2], "c": true});
+
+        let result = merge(&base, &ours, &theirs);
+
+        assert!(result.is_clean());
+        assert_eq!(
+            result.merged(),
+            Some(json!({"a": 2, "b": [1, 2], "c": true}))
+        );
+    }
+
+    #[test]
+    fn merge_arrays() {
+        let base = json!({"a": [1, 2, 3]});
+        let ours = json!({"a": [0, 2]});
+        let theirs = json!({"a": [1, 2, 3, 4]});
+
+        let result = merge(&base, &ours, &theirs);
+
+        assert!(result.is_clean());
+        assert_eq!(result.merged(), Some(json!({"a": [0, 2, 4]})));
+    }
+
+    #[test]
+    fn merge_conflicts() {
+        let base = json!({"a": 1, "b": {"c": 1}, "d": [1, 2], "e": 1});
+        let ours = json!({"a": 2, "b": {"c": 2}, "d": [1, 2, 3], "e": 2});
+        let theirs = json!({"a": 3, "b": 1, "d": [1, 2, 4], "e": 2});
+
+        let result = merge(&base, &ours, &theirs);
+
+        assert!(!result.is_clean());
+        assert_eq!(result.merged(), None);
+        assert_eq!(
+            result.conflicts(),
+            &[
+                Conflict {
+                    path: "$.a".to_string(),
+                    base: Some(json!(1)),
+                    ours: Some(json!(2)),
+                    theirs: Some(json!(3)),
+                },
+                Conflict {
+                    path: "$.b".to_string(),
+                    base: Some(json!({"c": 1})),
+                    ours: Some(json!({"c": 2})),
+                    theirs: Some(json!(1)),
+                },
+                Conflict {
+                    path: "$.d".to_string(),
+                    base: Some(json!([1, 2])),
+                    ours: Some(json!([1, 2, 3])),
+                    theirs: Some(json!([1, 2, 4])),
+                },
+            ]
+        );
+
+        assert_eq!(
+            result.resolve(Strategy::Ours),
+            json!({"a": 2, "b": {"c": 2}, "d": [1, 2, 3], "e": 2})
+        );
+        assert_eq!(
+            result.resolve(Strategy::Theirs),
+            json!({"a": 3, "b": 1, "d": [1, 2, 4], "e": 2})
+        );
+    }
+
+    #[test]
+    fn merge_conflict_with_deletion() {
+        let base = json!({"a": {"b": 1, "c": 1}, "d": 1});
+        let ours = json!({"d": 1});
+        let theirs = json!({"a": {"b": 2, "c": 1}, "d": 1});
+
+        let result = merge(&base, &ours, &theirs);
+
+        assert_eq!(result.conflicts().len(), 1);
+        assert_eq!(result.conflicts()[0].path, "$.a");
+        assert_eq!(result.conflicts()[0].ours, None);
+        assert_eq!(result.resolve(Strategy::Ours), json!({"d": 1}));
+        assert_eq!(
+            result.resolve(Strategy::Theirs),
+            json!({"a": {"b": 2, "c": 1}, "d": 1})
+        );
+    }
+
+    #[test]
+    fn merge_keeps_emptied_objects() {
+        let base = json!({"a": {"x": 1, "y": 2}});
+        let ours = json!({"a": {"y": 2}});
+        let theirs = json!({"a": {"x": 1}});
+
+        let result = merge(&base, &ours, &theirs);
+
+        assert!(result.is_clean());
+        assert_eq!(result.merged(), Some(json!({"a": {}})));
+    }
+
+    #[test]
+    fn merge_with_closure() {
+        let base = json!({"count": 1, "name": "a"});
+        let ours = json!({"count": 3, "name": "b"});
+        let theirs = json!({"count": 2, "name": "c"});
+
+        let result = merge(&base, &ours, &theirs);
+
+        let merged = result.resolve(|conflict: &Conflict| match conflict.path.as_str() {
+            "$.count" => Some(json!(
+                conflict.ours.as_ref().and_then(Value::as_i64).unwrap_or(0)
+                    + conflict
+                        .theirs
+                        .as_ref()
+                        .and_then(Value::as_i64)
+                        .unwrap_or(0)
+            )),
+            _ => None,
+        });
+
+        assert_eq!(merged, json!({"count": 5}));
+    }
+
+    #[test]
+    fn merge_conflicting_additions() {
+        let base = json!({});
+        let ours = json!({"a": [1], "b": {"c": 1}});
+        let theirs = json!({"a": {"d": 1}, "b": {"e": 1}});
+
+        let result = merge(&base, &ours, &theirs);
+
+        let paths: Vec<&str> = result
+            .conflicts()
+            .iter()
+            .map(|conflict| conflict.path.as_str())
+            .collect();
+        assert_eq!(paths, vec!["$.a", "$.b"]);
+        assert_eq!(result.conflicts()[0].base, None);
+        assert_eq!(result.resolve(Strategy::Theirs), theirs);
+    }
+}
@@ -0,0 +1,39 @@
+//! MessagePack documents, behind the `msgpack` feature.
+//!
+//! Documents are decoded to `Value`, so binary data, extension types and maps
+//! with keys that are not strings are rejected. Delta lists can be stored in
+//! the compact form of `delta::compact`.
+
+use crate::delta::compact::{Compact, CompactRef};
+use crate::delta::Delta;
+use crate::errors::ProcessError;
+use serde_json::Value;
+
+/// Decodes a MessagePack document into a `Value`
+pub fn from_slice(bytes: &[u8]) -> Result<Value, ProcessError> {
+    rmp_serde::from_slice(bytes).map_err(|error| invalid(error.to_string()))
+}
+
+/// Encodes a `Value` as a MessagePack document
+pub fn to_vec(value: &Value) -> Result<Vec<u8>, ProcessError> {
+    rmp_serde::to_vec(value).map_err(|error| invalid(error.to_string()))
+}
+
+/// Encodes deltas in their compact form
+pub fn deltas_to_vec(deltas: &[Delta]) -> Result<Vec<u8>, ProcessError> {
+    rmp_serde::to_vec(&CompactRef(deltas)).map_err(|error| invalid(error.to_string()))
+}
+
+/// Decodes deltas encoded with `deltas_to_vec`
+pub fn deltas_from_slice(bytes: &[u8]) -> Result<Vec<Delta>, ProcessError> {
+    let Compact(deltas) =
+        rmp_serde::from_slice(bytes).map_err(|error| invalid(error.to_string()))?;
+    Ok(deltas)
+}
+
+fn invalid(message: String) -> ProcessError {
+    ProcessError::InvalidDocument {
+        format: "MessagePack".to_string(),
+        message,
+    }
+}
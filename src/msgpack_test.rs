@@ -0,0 +1,45 @@
+#[cfg(test)]
+mod tests {
+    use crate::differ::Differ;
+    use crate::msgpack;
+    use crate::patcher::{patch, PatchOptions};
+    use serde_json::{json, Value};
+
+    #[test]
+    fn msgpack_diff_and_patch() {
+        let left = json!({"id": 7, "tags": ["a"], "price": 1.5, "meta": null});
+        let right = json!({"id": 7, "tags": ["a", "b"], "price": 2.5, "meta": {"x": true}});
+        let (left_bytes, right_bytes) = (
+            msgpack::to_vec(&left).unwrap(),
+            msgpack::to_vec(&right).unwrap(),
+        );
+
+        let mut differ = Differ::new_from_msgpack(&left_bytes, &right_bytes).unwrap();
+        let deltas = differ.diff().get_deltas().clone();
+        let patched = patch(
+            msgpack::from_slice(&left_bytes).unwrap(),
+            &deltas,
+            PatchOptions::default(),
+        );
+
+        assert_eq!(
+            msgpack::from_slice(&msgpack::to_vec(&patched).unwrap()).unwrap(),
+            right
+        );
+        assert!(msgpack::from_slice(&[0xc1]).is_err());
+    }
+
+    #[test]
+    fn msgpack_compact_deltas() {
+        let left = json!({"items": [{"name": "first", "count": 1}]});
+        let right = json!({"items": [{"name": "first", "count": 2}, {"name": "second"}]});
+        let mut differ = Differ::new_from_json_values(left, right);
+        let deltas = differ.diff().get_deltas().clone();
+
+        let bytes = msgpack::deltas_to_vec(&deltas).unwrap();
+
+        assert_eq!(msgpack::deltas_from_slice(&bytes).unwrap(), deltas);
+        assert!(bytes.len() * 3 < serde_json::to_vec(&deltas).unwrap().len());
+        assert!(msgpack::deltas_from_slice(&msgpack::to_vec(&Value::from(1)).unwrap()).is_err());
+    }
+}
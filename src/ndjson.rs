@@ -0,0 +1,293 @@
+//! Record by record diff of NDJSON, or JSON Lines, sources.
+//!
+//! Every line holds a record, and records of both sources are matched by the
+//! value of a key expression or by their line number. Only an index of the
+//! keys of the left source, with the offset of their line, is kept in memory:
+//! the right source is read once, and left records are read again when they
+//! are needed.
+
+use crate::delta::Delta;
+use crate::differ::Differ;
+use crate::errors::ProcessError;
+use serde_json::Value;
+use serde_json_path::JsonPath;
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::ops::ControlFlow;
+
+/// How the records of both sources are matched
+#[derive(Debug, Clone)]
+pub enum RecordKey {
+    /// Records on the same line are compared, the key is the line number
+    Line,
+    /// Records with the same value at a JSONPath expression, such as `$.id`,
+    /// are compared. The key is the JSON text of the value.
+    Path(JsonPath),
+}
+
+impl RecordKey {
+    /// Parses a JSONPath expression
+    pub fn path(expression: &str) -> Result<RecordKey, ProcessError> {
+        JsonPath::parse(expression)
+            .map(RecordKey::Path)
+            .map_err(|error| invalid(format!("key {}: {}", expression, error)))
+    }
+}
+
+/// A difference between two sources
+#[derive(Debug, PartialEq, Clone)]
+pub enum RecordDelta {
+    Added { key: String, record: Value },
+    Removed { key: String, record: Value },
+    Changed { key: String, deltas: Vec<Delta> },
+}
+
+/// A line of the left source in the key index
+struct Entry {
+    offset: u64,
+    line: usize,
+    matched: bool,
+}
+
+/// Compares the records of two NDJSON sources
+pub struct RecordDiffer<L: Read + Seek, R: Read> {
+    left: Lines<L>,
+    right: Lines<R>,
+    key: RecordKey,
+}
+
+impl<L: Read + Seek, R: Read> RecordDiffer<L, R> {
+    pub fn new(left: L, right: R, key: RecordKey) -> Self {
+        RecordDiffer {
+            left: Lines::new(left),
+            right: Lines::new(right),
+            key,
+        }
+    }
+
+    /// Compares the sources and calls `callback` for every record added,
+    /// removed or changed. Changed records come with the deltas between both
+    /// versions, with paths relative to the record.
+    ///
+    /// Records are reported in the order of the right source, followed by the
+    /// records only found in the left one. Returning `ControlFlow::Break` from
+    /// the callback stops reading the sources.
+    pub fn diff<F>(&mut self, mut callback: F) -> Result<(), ProcessError>
+    where
+        F: FnMut(RecordDelta) -> ControlFlow<()>,
+    {
+        match self.key.clone() {
+            RecordKey::Line => self.diff_lines(&mut callback),
+            RecordKey::Path(path) => self.diff_keys(&path, &mut callback),
+        }
+    }
+
+    /// Compares the records on the same lines, reading both sources once
+    fn diff_lines<F>(&mut self, callback: &mut F) -> Result<(), ProcessError>
+    where
+        F: FnMut(RecordDelta) -> ControlFlow<()>,
+    {
+        loop {
+            let (left, right) = (self.left.next()?, self.right.next()?);
+            let delta = match (left, right) {
+                (None, None) => return Ok(()),
+                (Some(left), Some(right)) => {
+                    let key = left.line.to_string();
+                    match (left.record, right.record) {
+                        (Some(old), Some(new)) => changed(key, old, new),
+                        (Some(record), None) => Some(RecordDelta::Removed { key, record }),
+                        (None, Some(record)) => Some(RecordDelta::Added { key, record }),
+                        (None, None) => None,
+                    }
+                }
+                (Some(line), None) => line.record.map(|record| RecordDelta::Removed {
+                    key: line.line.to_string(),
+                    record,
+                }),
+                (None, Some(line)) => line.record.map(|record| RecordDelta::Added {
+                    key: line.line.to_string(),
+                    record,
+                }),
+            };
+
+            if let Some(delta) = delta {
+                if callback(delta).is_break() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Indexes the keys of the left source, then matches the records of the
+    /// right one against it
+    fn diff_keys<F>(&mut self, path: &JsonPath, callback: &mut F) -> Result<(), ProcessError>
+    where
+        F: FnMut(RecordDelta) -> ControlFlow<()>,
+    {
+        let mut index: HashMap<String, Entry> = HashMap::new();
+        while let Some(line) = self.left.next()? {
+            let Some(record) = line.record else {
+                continue;
+            };
+            let key = record_key(path, &record, line.line)?;
+            let entry = Entry {
+                offset: line.offset,
+                line: line.line,
+                matched: false,
+            };
+            if let Some(other) = index.insert(key.clone(), entry) {
+                return Err(invalid(format!(
+                    "lines {} and {} have the same key {}",
+                    other.line, line.line, key
+                )));
+            }
+        }
+
+        let mut seen: HashSet<String> = HashSet::new();
+        while let Some(line) = self.right.next()? {
+            let Some(record) = line.record else {
+                continue;
+            };
+            let key = record_key(path, &record, line.line)?;
+            if !seen.insert(key.clone()) {
+                return Err(invalid(format!(
+                    "line {} repeats the key {}",
+                    line.line, key
+                )));
+            }
+
+            let delta = match index.get_mut(&key) {
+                Some(entry) => {
+                    entry.matched = true;
+                    let old = self.left.read_at(entry.offset, entry.line)?;
+                    changed(key, old, record)
+                }
+                None => Some(RecordDelta::Added { key, record }),
+            };
+
+            if let Some(delta) = delta {
+                if callback(delta).is_break() {
+                    return Ok(());
+                }
+            }
+        }
+
+        let mut removed: Vec<(String, Entry)> = index
+            .into_iter()
+            .filter(|(_, entry)| !entry.matched)
+            .collect();
+        removed.sort_by_key(|(_, entry)| entry.offset);
+
+        for (key, entry) in removed {
+            let record = self.left.read_at(entry.offset, entry.line)?;
+            if callback(RecordDelta::Removed { key, record }).is_break() {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the `Changed` delta between two versions of a record, if any
+fn changed(key: String, old: Value, new: Value) -> Option<RecordDelta> {
+    let mut differ = Differ::new_from_json_values(old, new);
+    let deltas = differ.diff().get_deltas().clone();
+    (!deltas.is_empty()).then_some(RecordDelta::Changed { key, deltas })
+}
+
+/// Returns the JSON text of the value at `path`, or of the array of values
+/// when the expression selects several of them
+fn record_key(path: &JsonPath, record: &Value, line: usize) -> Result<String, ProcessError> {
+    let nodes = path.query(record).all();
+    match nodes.as_slice() {
+        [] => Err(invalid(format!("line {} has no key", line))),
+        [node] => Ok(node.to_string()),
+        nodes => Ok(Value::from_iter(nodes.iter().map(|node| (*node).clone())).to_string()),
+    }
+}
+
+/// A line of a source, `record` is `None` for blank lines
+struct Line {
+    offset: u64,
+    line: usize,
+    record: Option<Value>,
+}
+
+/// Reads the lines of a source one at a time
+struct Lines<S: Read> {
+    reader: BufReader<S>,
+    buffer: String,
+    offset: u64,
+    line: usize,
+}
+
+impl<S: Read> Lines<S> {
+    fn new(source: S) -> Self {
+        Lines {
+            reader: BufReader::new(source),
+            buffer: String::new(),
+            offset: 0,
+            line: 0,
+        }
+    }
+
+    fn next(&mut self) -> Result<Option<Line>, ProcessError> {
+        self.buffer.clear();
+        let length = self.reader.read_line(&mut self.buffer).map_err(io_error)?;
+        if length == 0 {
+            return Ok(None);
+        }
+
+        let line = Line {
+            offset: self.offset,
+            line: self.line + 1,
+            record: parse(&self.buffer, self.offset)?,
+        };
+        self.offset += length as u64;
+        self.line += 1;
+
+        Ok(Some(line))
+    }
+}
+
+impl<S: Read + Seek> Lines<S> {
+    /// Reads the record at the given offset again, the next call to `next`
+    /// must not follow
+    fn read_at(&mut self, offset: u64, line: usize) -> Result<Value, ProcessError> {
+        self.reader
+            .seek(SeekFrom::Start(offset))
+            .map_err(io_error)?;
+        self.buffer.clear();
+        self.reader.read_line(&mut self.buffer).map_err(io_error)?;
+
+        parse(&self.buffer, offset)?
+            .ok_or_else(|| invalid(format!("line {} changed while reading", line)))
+    }
+}
+
+fn parse(text: &str, offset: u64) -> Result<Option<Value>, ProcessError> {
+    if text.trim().is_empty() {
+        return Ok(None);
+    }
+
+    serde_json::from_str(text)
+        .map(Some)
+        .map_err(|error| ProcessError::InvalidJson {
+            offset: offset as usize + error.column().saturating_sub(1),
+            message: error.to_string(),
+        })
+}
+
+fn invalid(message: String) -> ProcessError {
+    ProcessError::InvalidDocument {
+        format: "NDJSON".to_string(),
+        message,
+    }
+}
+
+fn io_error(error: std::io::Error) -> ProcessError {
+    ProcessError::Io {
+        message: error.to_string(),
+    }
+}
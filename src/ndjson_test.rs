@@ -0,0 +1,94 @@
+#[cfg(test)]
+mod tests {
+    use crate::delta::{Delta, Operation};
+    use crate::ndjson::{RecordDelta, RecordDiffer, RecordKey};
+    use serde_json::{json, Value};
+    use std::io::Cursor;
+    use std::ops::ControlFlow;
+
+    const LEFT: &str = r#"{"id": 1, "name": "ann"}
+{"id": 2, "name": "bob"}
+
+{"id": 3, "name": "cid"}
+"#;
+
+    const RIGHT: &str = r#"{"id": 3, "name": "cid"}
+{"id": 1, "name": "anne"}
+{"id": 4, "name": "dan"}"#;
+
+    fn diff(left: &str, right: &str, key: RecordKey) -> Vec<RecordDelta> {
+        let mut differ = RecordDiffer::new(Cursor::new(left), right.as_bytes(), key);
+        let mut deltas = Vec::new();
+        differ
+            .diff(|delta| {
+                deltas.push(delta);
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+        deltas
+    }
+
+    #[test]
+    fn ndjson_diff_by_key() {
+        assert_eq!(
+            diff(LEFT, RIGHT, RecordKey::path("$.id").unwrap()),
+            vec![
+                RecordDelta::Changed {
+                    key: "1".to_string(),
+                    deltas: vec![Delta::new(
+                        Operation::Change,
+                        "$.name".to_string(),
+                        json!("ann"),
+                        json!("anne")
+                    )],
+                },
+                RecordDelta::Added {
+                    key: "4".to_string(),
+                    record: json!({"id": 4, "name": "dan"}),
+                },
+                RecordDelta::Removed {
+                    key: "2".to_string(),
+                    record: json!({"id": 2, "name": "bob"}),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn ndjson_diff_by_line() {
+        let keys: Vec<(String, Option<Value>)> = diff(LEFT, RIGHT, RecordKey::Line)
+            .into_iter()
+            .map(|delta| match delta {
+                RecordDelta::Added { key, record } => (key, Some(record)),
+                RecordDelta::Removed { key, .. } | RecordDelta::Changed { key, .. } => (key, None),
+            })
+            .collect();
+
+        assert_eq!(
+            keys,
+            vec![
+                ("1".to_string(), None),
+                ("2".to_string(), None),
+                ("3".to_string(), Some(json!({"id": 4, "name": "dan"}))),
+                ("4".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn ndjson_diff_errors() {
+        let key = || RecordKey::path("$.id").unwrap();
+        let run = |left: &str, right: &str| {
+            RecordDiffer::new(Cursor::new(left.to_string()), right.as_bytes(), key())
+                .diff(|_| ControlFlow::Continue(()))
+        };
+
+        assert!(RecordKey::path("id").is_err());
+        assert!(run("{\"id\": 1}\n{\"id\": 1}\n", "").is_err());
+        assert!(run("{\"name\": 1}\n", "").is_err());
+        assert!(run("{\"id\": 1}\n", "{\"id\": 1}\n{\"id\": 1}\n").is_err());
+        assert!(run("", "{\"id\": 2}\n{\"id\": 2}\n").is_err());
+        assert!(run("{\"id\": 1}\n", "{\"id\": \n").is_err());
+        assert!(run("{\"id\": 1}\n", "{\"id\": 1}\n").is_ok());
+    }
+}
@@ -0,0 +1,677 @@
+//! Changelog of two versions of an OpenAPI 3 document.
+//!
+//! Operations are matched by method and path, ignoring the names of path
+//! parameters, and parameters by name and location, instead of by their
+//! position in arrays. Request bodies and responses are matched by status
+//! code and media type, and their schemas are compared with
+//! `schema::diff_schemas`: request schemas as written by the API consumers,
+//! response schemas as read by them. Local `$ref`s are resolved, those of
+//! schemas as the comparison reaches them.
+
+use crate::errors::ProcessError;
+use crate::schema::{diff_referenced_schemas, References, Schema, SchemaChange, Severity, Usage};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+const METHODS: [&str; 8] = [
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+
+/// What changed in an operation
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum ChangeKind {
+    EndpointAdded,
+    EndpointRemoved,
+    ParameterAdded {
+        name: String,
+        location: String,
+        required: bool,
+    },
+    ParameterRemoved {
+        name: String,
+        location: String,
+    },
+    ParameterRequired {
+        name: String,
+        location: String,
+    },
+    ParameterOptional {
+        name: String,
+        location: String,
+    },
+    ParameterSchema {
+        name: String,
+        location: String,
+        change: SchemaChange,
+    },
+    RequestBodyAdded {
+        required: bool,
+    },
+    RequestBodyRemoved,
+    RequestBodyRequired,
+    RequestBodyOptional,
+    RequestSchema {
+        media_type: String,
+        change: SchemaChange,
+    },
+    ResponseAdded {
+        status: String,
+    },
+    ResponseRemoved {
+        status: String,
+    },
+    ResponseSchema {
+        status: String,
+        media_type: String,
+        change: SchemaChange,
+    },
+    /// A media type of the request body, when `status` is `None`, or of a
+    /// response
+    MediaTypeAdded {
+        status: Option<String>,
+        media_type: String,
+    },
+    MediaTypeRemoved {
+        status: Option<String>,
+        media_type: String,
+    },
+}
+
+/// A change of an operation, such as `GET /users/{id}`
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Change {
+    pub endpoint: String,
+    pub kind: ChangeKind,
+    pub severity: Severity,
+}
+
+/// The changes between two versions of a document, by endpoint
+#[derive(Debug, Default, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Changelog {
+    pub changes: Vec<Change>,
+}
+
+impl Changelog {
+    /// Returns true if a change breaks the API consumers
+    pub fn is_breaking(&self) -> bool {
+        self.changes
+            .iter()
+            .any(|change| change.severity == Severity::Breaking)
+    }
+
+    /// Returns the breaking changes
+    pub fn breaking(&self) -> impl Iterator<Item = &Change> {
+        self.changes
+            .iter()
+            .filter(|change| change.severity == Severity::Breaking)
+    }
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChangeKind::EndpointAdded => write!(f, "endpoint added"),
+            ChangeKind::EndpointRemoved => write!(f, "endpoint removed"),
+            ChangeKind::ParameterAdded {
+                name,
+                location,
+                required,
+            } => {
+                let required = if *required { "required" } else { "optional" };
+                write!(f, "{} {} parameter {} added", required, location, name)
+            }
+            ChangeKind::ParameterRemoved { name, location } => {
+                write!(f, "{} parameter {} removed", location, name)
+            }
+            ChangeKind::ParameterRequired { name, location } => {
+                write!(f, "{} parameter {} is now required", location, name)
+            }
+            ChangeKind::ParameterOptional { name, location } => {
+                write!(f, "{} parameter {} is now optional", location, name)
+            }
+            ChangeKind::ParameterSchema {
+                name,
+                location,
+                change,
+            } => write!(
+                f,
+                "{} parameter {} {}: {}",
+                location, name, change.path, change.kind
+            ),
+            ChangeKind::RequestBodyAdded { required: true } => {
+                write!(f, "required request body added")
+            }
+            ChangeKind::RequestBodyAdded { required: false } => {
+                write!(f, "optional request body added")
+            }
+            ChangeKind::RequestBodyRemoved => write!(f, "request body removed"),
+            ChangeKind::RequestBodyRequired => write!(f, "request body is now required"),
+            ChangeKind::RequestBodyOptional => write!(f, "request body is now optional"),
+            ChangeKind::RequestSchema { media_type, change } => {
+                write!(f, "request {} {}: {}", media_type, change.path, change.kind)
+            }
+            ChangeKind::ResponseAdded { status } => write!(f, "response {} added", status),
+            ChangeKind::ResponseRemoved { status } => write!(f, "response {} removed", status),
+            ChangeKind::ResponseSchema {
+                status,
+                media_type,
+                change,
+            } => write!(
+                f,
+                "response {} {} {}: {}",
+                status, media_type, change.path, change.kind
+            ),
+            ChangeKind::MediaTypeAdded { status, media_type } => match status {
+                Some(status) => write!(f, "response {} media type {} added", status, media_type),
+                None => write!(f, "request media type {} added", media_type),
+            },
+            ChangeKind::MediaTypeRemoved { status, media_type } => match status {
+                Some(status) => write!(f, "response {} media type {} removed", status, media_type),
+                None => write!(f, "request media type {} removed", media_type),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} ({})", self.endpoint, self.kind, self.severity)
+    }
+}
+
+/// Lists the breaking changes first, one per line
+impl fmt::Display for Changelog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (breaking, other): (Vec<&Change>, Vec<&Change>) = self
+            .changes
+            .iter()
+            .partition(|change| change.severity == Severity::Breaking);
+
+        for (title, changes) in [("Breaking changes", breaking), ("Other changes", other)] {
+            if changes.is_empty() {
+                continue;
+            }
+            writeln!(f, "{}:", title)?;
+            for change in changes {
+                writeln!(f, "- {}: {}", change.endpoint, change.kind)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Compares two OpenAPI 3 documents. Endpoints are listed in the order of
+/// their paths, then methods.
+pub fn diff(old: &Value, new: &Value) -> Result<Changelog, ProcessError> {
+    let (old_operations, old_references) = operations(old)?;
+    let (new_operations, new_references) = operations(new)?;
+    let references = (&old_references, &new_references);
+
+    let keys: BTreeSet<&(String, String)> =
+        old_operations.keys().chain(new_operations.keys()).collect();
+
+    let mut changes = Vec::new();
+    for key in keys {
+        let (old_operation, new_operation) = (old_operations.get(key), new_operations.get(key));
+        let endpoint = match new_operation.or(old_operation) {
+            Some(operation) => operation.endpoint.clone(),
+            None => continue,
+        };
+        let mut push = |kind: ChangeKind, severity: Severity| {
+            changes.push(Change {
+                endpoint: endpoint.clone(),
+                kind,
+                severity,
+            })
+        };
+
+        match (old_operation, new_operation) {
+            (Some(_), None) => push(ChangeKind::EndpointRemoved, Severity::Breaking),
+            (None, Some(_)) => push(ChangeKind::EndpointAdded, Severity::NonBreaking),
+            (Some(old), Some(new)) => compare_operations(old, new, references, &mut push)?,
+            (None, None) => {}
+        }
+    }
+
+    Ok(Changelog { changes })
+}
+
+/// An operation with its references resolved, outside of schemas
+struct Operation {
+    endpoint: String,
+    /// By name and location
+    parameters: BTreeMap<(String, String), Value>,
+    request_body: Option<Value>,
+    responses: BTreeMap<String, Value>,
+}
+
+/// Operations by path template and method
+type Operations = BTreeMap<(String, String), Operation>;
+
+/// Returns the operations of a document by method and path, with the names
+/// of path parameters left out of the path, and the schemas their `$ref`s
+/// point to
+fn operations(document: &Value) -> Result<(Operations, References), ProcessError> {
+    match document.get("openapi") {
+        Some(Value::String(version)) if version.starts_with("3.") => {}
+        _ => return Err(invalid("not an OpenAPI 3 document".to_string())),
+    }
+    let paths = match document.get("paths") {
+        Some(Value::Object(paths)) => paths,
+        None => return Ok((BTreeMap::new(), References::new())),
+        Some(_) => return Err(invalid("paths is not an object".to_string())),
+    };
+
+    let mut operations = BTreeMap::new();
+    for (path, item) in paths {
+        let item = resolve(document, item, &mut Vec::new());
+        let shared = parameters(document, item.get("parameters"));
+
+        for method in METHODS {
+            let Some(operation) = item.get(method) else {
+                continue;
+            };
+
+            let mut parameters = shared.clone();
+            parameters.extend(self::parameters(document, operation.get("parameters")));
+            let parameters = parameters
+                .into_iter()
+                .map(|((name, location), parameter)| {
+                    // path parameters are matched by position, as the path
+                    match path_position(path, &name) {
+                        Some(position) if location == "path" => {
+                            ((format!("{{{}}}", position), location), parameter)
+                        }
+                        _ => ((name, location), parameter),
+                    }
+                })
+                .collect();
+
+            let request_body = operation
+                .get("requestBody")
+                .map(|body| resolve(document, body, &mut Vec::new()));
+            let responses = match operation.get("responses") {
+                Some(Value::Object(responses)) => responses
+                    .iter()
+                    .map(|(status, response)| {
+                        (status.clone(), resolve(document, response, &mut Vec::new()))
+                    })
+                    .collect(),
+                _ => BTreeMap::new(),
+            };
+
+            let key = (template(path), method.to_string());
+            operations.insert(
+                key,
+                Operation {
+                    endpoint: format!("{} {}", method.to_uppercase(), path),
+                    parameters,
+                    request_body,
+                    responses,
+                },
+            );
+        }
+    }
+
+    let schemas = operations.values().flat_map(|operation| {
+        let contents = operation
+            .request_body
+            .iter()
+            .chain(operation.responses.values())
+            .filter_map(|value| value.get("content")?.as_object())
+            .flat_map(|content| content.values());
+        operation.parameters.values().chain(contents)
+    });
+    let references = references(document, schemas.filter_map(|value| value.get("schema")))?;
+
+    Ok((operations, references))
+}
+
+/// Parses the schemas the local `$ref`s of the given schemas point to,
+/// directly or through other schemas
+fn references<'a>(
+    document: &Value,
+    schemas: impl Iterator<Item = &'a Value>,
+) -> Result<References, ProcessError> {
+    let mut references = References::new();
+    // the schemas themselves are parsed again when compared
+    let mut pending: Vec<String> = schemas
+        .filter_map(|schema| Schema::from_value(schema).ok())
+        .flat_map(|schema| {
+            schema
+                .references()
+                .into_iter()
+                .map(String::from)
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    while let Some(reference) = pending.pop() {
+        if references.contains_key(&reference) {
+            continue;
+        }
+        let Some(target) = reference
+            .strip_prefix('#')
+            .and_then(|pointer| document.pointer(pointer))
+        else {
+            continue;
+        };
+
+        let schema = Schema::from_value(target)?;
+        pending.extend(schema.references().into_iter().map(String::from));
+        references.insert(reference, schema);
+    }
+
+    Ok(references)
+}
+
+/// Returns the parameters of a list by name and location
+fn parameters(document: &Value, list: Option<&Value>) -> BTreeMap<(String, String), Value> {
+    let Some(Value::Array(list)) = list else {
+        return BTreeMap::new();
+    };
+
+    list.iter()
+        .map(|parameter| resolve(document, parameter, &mut Vec::new()))
+        .filter_map(|parameter| {
+            let name = parameter.get("name")?.as_str()?.to_string();
+            let location = parameter.get("in")?.as_str()?.to_string();
+            Some(((name, location), parameter))
+        })
+        .collect()
+}
+
+fn compare_operations<F>(
+    old: &Operation,
+    new: &Operation,
+    references: (&References, &References),
+    push: &mut F,
+) -> Result<(), ProcessError>
+where
+    F: FnMut(ChangeKind, Severity),
+{
+    let keys: BTreeSet<&(String, String)> =
+        old.parameters.keys().chain(new.parameters.keys()).collect();
+    for key in keys {
+        let (old_parameter, new_parameter) = (old.parameters.get(key), new.parameters.get(key));
+        let name = new_parameter
+            .or(old_parameter)
+            .and_then(|parameter| parameter.get("name"))
+            .and_then(Value::as_str)
+            .unwrap_or(&key.0)
+            .to_string();
+        let location = key.1.clone();
+        match (old_parameter, new_parameter) {
+            (Some(_), None) => push(
+                ChangeKind::ParameterRemoved { name, location },
+                Severity::NonBreaking,
+            ),
+            (None, Some(parameter)) => {
+                let required = is_required(parameter);
+                push(
+                    ChangeKind::ParameterAdded {
+                        name,
+                        location,
+                        required,
+                    },
+                    severity(required),
+                )
+            }
+            (Some(old), Some(new)) => {
+                match (is_required(old), is_required(new)) {
+                    (false, true) => push(
+                        ChangeKind::ParameterRequired {
+                            name: name.clone(),
+                            location: location.clone(),
+                        },
+                        Severity::Breaking,
+                    ),
+                    (true, false) => push(
+                        ChangeKind::ParameterOptional {
+                            name: name.clone(),
+                            location: location.clone(),
+                        },
+                        Severity::NonBreaking,
+                    ),
+                    _ => {}
+                }
+                for change in compare_schemas(
+                    old.get("schema"),
+                    new.get("schema"),
+                    Usage::Write,
+                    references,
+                )? {
+                    let severity = change.severity;
+                    let kind = ChangeKind::ParameterSchema {
+                        name: name.clone(),
+                        location: location.clone(),
+                        change,
+                    };
+                    push(kind, severity);
+                }
+            }
+            (None, None) => {}
+        }
+    }
+
+    match (&old.request_body, &new.request_body) {
+        (Some(_), None) => push(ChangeKind::RequestBodyRemoved, Severity::Breaking),
+        (None, Some(body)) => {
+            let required = is_required(body);
+            push(
+                ChangeKind::RequestBodyAdded { required },
+                severity(required),
+            )
+        }
+        (Some(old), Some(new)) => {
+            match (is_required(old), is_required(new)) {
+                (false, true) => push(ChangeKind::RequestBodyRequired, Severity::Breaking),
+                (true, false) => push(ChangeKind::RequestBodyOptional, Severity::NonBreaking),
+                _ => {}
+            }
+            compare_content(old, new, None, references, push)?;
+        }
+        (None, None) => {}
+    }
+
+    let statuses: BTreeSet<&String> = old.responses.keys().chain(new.responses.keys()).collect();
+    for status in statuses {
+        match (old.responses.get(status), new.responses.get(status)) {
+            (Some(_), None) => push(
+                ChangeKind::ResponseRemoved {
+                    status: status.clone(),
+                },
+                Severity::Breaking,
+            ),
+            (None, Some(_)) => push(
+                ChangeKind::ResponseAdded {
+                    status: status.clone(),
+                },
+                Severity::NonBreaking,
+            ),
+            (Some(old), Some(new)) => compare_content(old, new, Some(status), references, push)?,
+            (None, None) => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Compares the schemas of the media types of a request body, when `status`
+/// is `None`, or of a response
+fn compare_content<F>(
+    old: &Value,
+    new: &Value,
+    status: Option<&String>,
+    references: (&References, &References),
+    push: &mut F,
+) -> Result<(), ProcessError>
+where
+    F: FnMut(ChangeKind, Severity),
+{
+    let content = |value: &Value| -> Map<String, Value> {
+        value
+            .get("content")
+            .and_then(Value::as_object)
+            .cloned()
+            .unwrap_or_default()
+    };
+    let (old_content, new_content) = (content(old), content(new));
+    let usage = match status {
+        Some(_) => Usage::Read,
+        None => Usage::Write,
+    };
+
+    let media_types: BTreeSet<&String> = old_content.keys().chain(new_content.keys()).collect();
+    for media_type in media_types {
+        let (old_media, new_media) = (old_content.get(media_type), new_content.get(media_type));
+        let status = status.cloned();
+        let media_type = media_type.clone();
+
+        match (old_media, new_media) {
+            (Some(_), None) => push(
+                ChangeKind::MediaTypeRemoved { status, media_type },
+                Severity::Breaking,
+            ),
+            (None, Some(_)) => push(
+                ChangeKind::MediaTypeAdded { status, media_type },
+                Severity::NonBreaking,
+            ),
+            (Some(old), Some(new)) => {
+                for change in
+                    compare_schemas(old.get("schema"), new.get("schema"), usage, references)?
+                {
+                    let severity = change.severity;
+                    let kind = match &status {
+                        Some(status) => ChangeKind::ResponseSchema {
+                            status: status.clone(),
+                            media_type: media_type.clone(),
+                            change,
+                        },
+                        None => ChangeKind::RequestSchema {
+                            media_type: media_type.clone(),
+                            change,
+                        },
+                    };
+                    push(kind, severity);
+                }
+            }
+            (None, None) => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Compares two schemas, a missing schema allowing any value
+fn compare_schemas(
+    old: Option<&Value>,
+    new: Option<&Value>,
+    usage: Usage,
+    (old_references, new_references): (&References, &References),
+) -> Result<Vec<SchemaChange>, ProcessError> {
+    let any = Value::Bool(true);
+    let old = Schema::from_value(old.unwrap_or(&any))?;
+    let new = Schema::from_value(new.unwrap_or(&any))?;
+    Ok(diff_referenced_schemas(
+        &old,
+        &new,
+        usage,
+        old_references,
+        new_references,
+    ))
+}
+
+/// Replaces the local `$ref`s of a value with what they point to, except in
+/// schemas, which keep theirs for `diff_referenced_schemas` to follow. A
+/// reference to itself, directly or not, becomes the `true` schema.
+fn resolve(document: &Value, value: &Value, stack: &mut Vec<String>) -> Value {
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::String(reference)) = object.get("$ref") {
+                let Some(pointer) = reference.strip_prefix('#') else {
+                    return value.clone();
+                };
+                if stack.contains(reference) {
+                    return Value::Bool(true);
+                }
+                let Some(target) = document.pointer(pointer) else {
+                    return value.clone();
+                };
+
+                stack.push(reference.clone());
+                let resolved = resolve(document, target, stack);
+                stack.pop();
+                return resolved;
+            }
+
+            Value::Object(
+                object
+                    .iter()
+                    .map(|(key, value)| match key.as_str() {
+                        "schema" => (key.clone(), value.clone()),
+                        _ => (key.clone(), resolve(document, value, stack)),
+                    })
+                    .collect(),
+            )
+        }
+        Value::Array(elements) => Value::Array(
+            elements
+                .iter()
+                .map(|element| resolve(document, element, stack))
+                .collect(),
+        ),
+        value => value.clone(),
+    }
+}
+
+/// Returns the position of a parameter among the parameters of a path
+fn path_position(path: &str, name: &str) -> Option<usize> {
+    path.split('{')
+        .skip(1)
+        .position(|rest| rest.split('}').next() == Some(name))
+}
+
+/// Replaces the names of path parameters, `/users/{id}` becomes `/users/{}`
+fn template(path: &str) -> String {
+    let mut template = String::new();
+    let mut in_parameter = false;
+    for character in path.chars() {
+        match character {
+            '{' => {
+                in_parameter = true;
+                template.push(character);
+            }
+            '}' => {
+                in_parameter = false;
+                template.push(character);
+            }
+            _ if in_parameter => {}
+            _ => template.push(character),
+        }
+    }
+    template
+}
+
+fn is_required(value: &Value) -> bool {
+    value.get("required") == Some(&Value::Bool(true))
+        || value.get("in") == Some(&Value::from("path"))
+}
+
+fn severity(breaking: bool) -> Severity {
+    if breaking {
+        Severity::Breaking
+    } else {
+        Severity::NonBreaking
+    }
+}
+
+fn invalid(message: String) -> ProcessError {
+    ProcessError::InvalidDocument {
+        format: "OpenAPI".to_string(),
+        message,
+    }
+}
@@ -0,0 +1,245 @@
+#[cfg(test)]
+mod tests {
+    use crate::errors::ProcessError;
+    use crate::openapi::{diff, ChangeKind};
+    use crate::schema::Severity;
+    use serde_json::{json, Value};
+
+    fn old_api() -> Value {
+        json!({
+            "openapi": "3.0.3",
+            "paths": {
+                "/users": {
+                    "get": {
+                        "parameters": [
+                            {"name": "limit", "in": "query", "schema": {"type": "integer"}},
+                            {"name": "X-Trace", "in": "header"}
+                        ],
+                        "responses": {
+                            "200": {
+                                "content": {
+                                    "application/json": {
+                                        "schema": {
+                                            "type": "array",
+                                            "items": {"$ref": "#/components/schemas/User"}
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    },
+                    "post": {
+                        "requestBody": {"$ref": "#/components/requestBodies/User"},
+                        "responses": {"201": {"description": "created"}}
+                    }
+                },
+                "/users/{id}": {
+                    "parameters": [{"name": "id", "in": "path", "schema": {"type": "string"}}],
+                    "delete": {"responses": {"204": {"description": "deleted"}}},
+                    "get": {
+                        "responses": {
+                            "200": {
+                                "content": {
+                                    "application/json": {
+                                        "schema": {"$ref": "#/components/schemas/User"}
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            },
+            "components": {
+                "schemas": {
+                    "User": {
+                        "type": "object",
+                        "required": ["id"],
+                        "properties": {
+                            "id": {"type": "string"},
+                            "name": {"type": "string"},
+                            "friends": {"type": "array", "items": {"$ref": "#/components/schemas/User"}}
+                        }
+                    }
+                },
+                "requestBodies": {
+                    "User": {
+                        "content": {
+                            "application/json": {"schema": {"$ref": "#/components/schemas/User"}}
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    /// Renames a path parameter, removes an endpoint, reorders and adds
+    /// parameters, adds a response and changes the `User` schema, which is
+    /// recursive
+    fn new_api() -> Value {
+        let mut api = old_api();
+
+        let item = api["paths"]["/users/{id}"].take();
+        api["paths"]["/users/{userId}"] = item;
+        api["paths"]["/users/{userId}"]["parameters"][0]["name"] = json!("userId");
+        api["paths"]["/users/{userId}"]
+            .as_object_mut()
+            .unwrap()
+            .remove("delete");
+
+        let get = &mut api["paths"]["/users"]["get"];
+        get["parameters"] = json!([
+            {"name": "X-Trace", "in": "header"},
+            {"name": "limit", "in": "query", "schema": {"type": "integer"}},
+            {"name": "tenant", "in": "query", "required": true, "schema": {"type": "string"}}
+        ]);
+        get["responses"]["404"] = json!({"description": "missing"});
+
+        api["paths"]["/groups"] = json!({"get": {"responses": {}}});
+        api["components"]["schemas"]["User"]["properties"]
+            .as_object_mut()
+            .unwrap()
+            .remove("name");
+        api["components"]["schemas"]["User"]["required"] = json!(["id", "email"]);
+        api["components"]["schemas"]["User"]["properties"]["email"] = json!({"type": "string"});
+        api
+    }
+
+    #[test]
+    fn openapi_changelog() {
+        let changelog = diff(&old_api(), &new_api()).unwrap();
+
+        assert!(changelog.is_breaking());
+        assert_eq!(
+            changelog.to_string(),
+            "Breaking changes:
+- GET /users: required query parameter tenant added
+- GET /users: response 200 application/json $[*].name: property removed
+- POST /users: request application/json $.email: now required
+- DELETE /users/{id}: endpoint removed
+- GET /users/{userId}: response 200 application/json $.name: property removed
+Other changes:
+- GET /groups: endpoint added
+- GET /users: response 200 application/json $[*].email: property added
+- GET /users: response 200 application/json $[*].email: now required
+- GET /users: response 404 added
+- POST /users: request application/json $.email: property added
+- POST /users: request application/json $.name: property removed
+- GET /users/{userId}: response 200 application/json $.email: property added
+- GET /users/{userId}: response 200 application/json $.email: now required
+"
+        );
+    }
+
+    #[test]
+    fn openapi_parameters() {
+        let api = |parameters: Value, body: Value| {
+            json!({
+                "openapi": "3.1.0",
+                "paths": {"/items": {"post": {"parameters": parameters, "requestBody": body}}}
+            })
+        };
+        let old = api(
+            json!([
+                {"name": "a", "in": "query", "required": true},
+                {"name": "b", "in": "query"},
+                {"name": "c", "in": "query", "schema": {"enum": ["x"]}}
+            ]),
+            json!({"content": {"application/json": {}, "text/plain": {}}}),
+        );
+        let new = api(
+            json!([
+                {"name": "a", "in": "query"},
+                {"name": "b", "in": "query", "required": true},
+                {"name": "c", "in": "query", "schema": {"enum": ["x", "y"]}},
+                {"name": "a", "in": "header"}
+            ]),
+            json!({"required": true, "content": {"application/json": {}}}),
+        );
+
+        let changes: Vec<(ChangeKind, Severity)> = diff(&old, &new)
+            .unwrap()
+            .changes
+            .into_iter()
+            .map(|change| (change.kind, change.severity))
+            .collect();
+        let kinds: Vec<String> = changes.iter().map(|(kind, _)| kind.to_string()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                "optional header parameter a added",
+                "query parameter a is now optional",
+                "query parameter b is now required",
+                "query parameter c $: enum value \"y\" added",
+                "request body is now required",
+                "request media type text/plain removed",
+            ]
+        );
+        let severities: Vec<Severity> = changes.iter().map(|(_, severity)| *severity).collect();
+        assert_eq!(
+            severities,
+            vec![
+                Severity::NonBreaking,
+                Severity::NonBreaking,
+                Severity::Breaking,
+                Severity::NonBreaking,
+                Severity::Breaking,
+                Severity::Breaking,
+            ]
+        );
+
+        assert!(!diff(&old, &old).unwrap().is_breaking());
+        assert!(diff(&old, &old).unwrap().changes.is_empty());
+    }
+
+    #[test]
+    fn openapi_shared_references() {
+        // every level refers twice to the next one, 2^40 paths in total
+        let api = |leaf: Value| {
+            let mut schemas: serde_json::Map<String, Value> = (0..40)
+                .map(|level| {
+                    let next = json!({"$ref": format!("#/components/schemas/S{}", level + 1)});
+                    let schema = json!({"properties": {"a": next, "b": next}});
+                    (format!("S{}", level), schema)
+                })
+                .collect();
+            schemas.insert("S40".to_string(), leaf);
+            json!({
+                "openapi": "3.0.3",
+                "paths": {"/items": {"get": {"responses": {"200": {"content": {
+                    "application/json": {"schema": {"$ref": "#/components/schemas/S0"}}
+                }}}}}},
+                "components": {"schemas": schemas}
+            })
+        };
+
+        let changelog = diff(
+            &api(json!({"type": "string"})),
+            &api(json!({"type": "integer"})),
+        );
+        let changes = changelog.unwrap().changes;
+
+        assert_eq!(changes.len(), 1, "{:?}", changes);
+        let ChangeKind::ResponseSchema { change, .. } = &changes[0].kind else {
+            panic!("{:?}", changes[0]);
+        };
+        assert_eq!(change.path, format!("${}", ".a".repeat(40)));
+    }
+
+    #[test]
+    fn openapi_invalid_documents() {
+        for document in [
+            json!({"swagger": "2.0", "paths": {}}),
+            json!({"openapi": "3.0.0", "paths": []}),
+            json!({"openapi": "3.0.0", "paths": {"/a": {"get": {"parameters": [
+                {"name": "a", "in": "query", "schema": {"type": "text"}}
+            ]}}}}),
+        ] {
+            let result = diff(&document, &document);
+            assert!(
+                matches!(result, Err(ProcessError::InvalidDocument { .. })),
+                "{:?}",
+                result
+            );
+        }
+    }
+}
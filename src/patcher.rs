@@ -1,9 +1,16 @@
-use crate::delta::{Delta, Operation};
+use crate::delta::{compare_paths, Delta, Operation};
 use crate::errors::ProcessError;
+use crate::text;
 use crate::utils::{
-    analyse_path, change_element, insert_element, remove_element, remove_empty_levels,
+    change_element, insert_element, parse_path, path_prefixes, remove_element, remove_empty_levels,
+    Segment,
 };
 use serde_json::{json, Value};
+use std::collections::HashSet;
+
+/// Maximum number of keys and indices in the path of a delta, deep enough
+/// for any document `serde_json` parses with its default recursion limit
+pub const MAX_PATH_DEPTH: usize = 1024;
 
 #[derive(Clone, Copy)]
 pub struct PatchOptions {
@@ -36,25 +43,135 @@ impl PatchOptions {
     }
 }
 
-pub fn patch(base: Value, deltas: &Vec<Delta>, options: PatchOptions) -> Value {
-    let base_value = &mut base.clone();
+/// Applies the deltas to the base value.
+///
+/// Runs of consecutive additions and deletions are applied deletions first,
+/// from the highest array index down, then additions from the lowest index
+/// up, whatever order the deltas were diffed in, so `patch(a, diff(a, b))`
+/// gives back `b`. Deletion indices refer to the array before the run and
+/// addition indices to the array after it. Deltas whose paths are equal or
+/// nested in one another are applied in the order given.
+///
+/// # Panics
+///
+/// Panics if a delta does not apply to the value, see `try_patch`.
+pub fn patch(base: Value, deltas: &[Delta], options: PatchOptions) -> Value {
+    try_patch(base, deltas, options).unwrap()
+}
+
+/// Applies the deltas to JSON text and returns the patched text.
+///
+/// Only the spans of the values that change are rewritten: indentation, key
+/// order, number formatting, comments and trailing commas are kept
+/// everywhere else. New keys are added after the existing ones of their
+/// object, following the indentation of their siblings.
+///
+/// # Panics
+///
+/// Panics if the text is not valid JSON or a delta does not apply to it, see
+/// `try_patch_text`.
+pub fn patch_text(original: &str, deltas: &[Delta]) -> String {
+    try_patch_text(original, deltas).unwrap()
+}
+
+/// Same as `patch_text`, but returns `ProcessError::InvalidJson` when the
+/// text is not valid JSON, or JSON with comments and trailing commas, and
+/// the errors of `try_patch` when a delta does not apply.
+pub fn try_patch_text(original: &str, deltas: &[Delta]) -> Result<String, ProcessError> {
+    let node = text::parse(original)?;
+    let patched = try_patch(node.value(), deltas, PatchOptions::default())?;
+    Ok(text::rewrite(original, &node, &patched))
+}
+
+/// Parses JSON text the way `try_patch_text` reads it, comments and trailing
+/// commas included, and returns `ProcessError::InvalidJson` otherwise.
+pub fn parse_text(text: &str) -> Result<Value, ProcessError> {
+    Ok(text::parse(text)?.value())
+}
+
+/// Same as `patch`, but returns `ProcessError::InvalidPath` when the path of
+/// a delta is malformed, goes through a value of another kind, points past
+/// the end of an array or is deeper than `MAX_PATH_DEPTH`. Use it for deltas
+/// that come from outside, such as over the network.
+pub fn try_patch(
+    base: Value,
+    deltas: &[Delta],
+    options: PatchOptions,
+) -> Result<Value, ProcessError> {
+    let mut base_value = base;
 
-    for delta in deltas {
+    for delta in ordered(deltas) {
         patch_by_path(
-            base_value,
+            &mut base_value,
             delta.path.as_str(),
             &delta.new_value,
             delta.operation.clone(),
             options,
-        )
-        .unwrap()
+        )?;
     }
 
     if options.omit_empty {
-        remove_empty_levels(base_value);
+        remove_empty_levels(&mut base_value);
+    }
+
+    Ok(base_value)
+}
+
+/// Reorders runs of consecutive additions and deletions: deletions by
+/// descending path, followed by additions by ascending path. Changes are left
+/// in place, and so is the order of deltas whose paths are equal or nested,
+/// as each one reads what the one before it did.
+fn ordered(deltas: &[Delta]) -> Vec<&Delta> {
+    let mut ordered: Vec<&Delta> = Vec::with_capacity(deltas.len());
+    let is_change = |delta: &Delta| delta.operation == Operation::Change;
+
+    for run in deltas.chunk_by(|a, b| is_change(a) == is_change(b)) {
+        if is_change(&run[0]) {
+            ordered.extend(run);
+            continue;
+        }
+
+        for group in unrelated(run) {
+            let (mut additions, mut deletions): (Vec<&Delta>, Vec<&Delta>) = group
+                .iter()
+                .partition(|delta| delta.operation == Operation::Add);
+            deletions.sort_by(|a, b| compare_paths(&b.path, &a.path));
+            additions.sort_by(|a, b| compare_paths(&a.path, &b.path));
+
+            ordered.extend(deletions);
+            ordered.extend(additions);
+        }
+    }
+
+    ordered
+}
+
+/// Splits deltas into groups in which no path is equal to, or nested in,
+/// another one of the group.
+fn unrelated(deltas: &[Delta]) -> Vec<&[Delta]> {
+    let mut groups = Vec::new();
+    let mut start = 0;
+    let mut paths: HashSet<&str> = HashSet::new();
+    let mut prefixes: HashSet<&str> = HashSet::new();
+
+    for (index, delta) in deltas.iter().enumerate() {
+        let path = delta.path.as_str();
+        let related =
+            prefixes.contains(path) || path_prefixes(path).any(|prefix| paths.contains(prefix));
+
+        if related {
+            groups.push(&deltas[start..index]);
+            start = index;
+            paths.clear();
+            prefixes.clear();
+        }
+
+        paths.insert(path);
+        prefixes.extend(path_prefixes(path));
     }
 
-    base_value.clone()
+    groups.push(&deltas[start..]);
+    groups
 }
 
 pub(crate) fn patch_by_path(
@@ -64,149 +181,133 @@ pub(crate) fn patch_by_path(
     operation: Operation,
     options: PatchOptions,
 ) -> Result<(), ProcessError> {
-    let mut current = json;
-    let path_analysis = analyse_path(path);
-    let mut paths = path_analysis.iter().enumerate();
-    loop {
-        let path_item = paths.next();
+    let path_segments = parse_path(path).map_err(|message| invalid_path(path, message))?;
 
-        if path_item.is_none() {
-            return Ok(());
-        }
-
-        let path_item = path_item.unwrap();
-
-        let (_index, item) = path_item;
-
-        if item.key == "$" {
-            continue;
-        }
+    if path_segments.len() > MAX_PATH_DEPTH {
+        return Err(invalid_path(path, "it is too deep"));
+    }
 
-        if item.is_last && current.is_array() {
-            if !item.is_array {
-                let arr = current.as_array_mut().unwrap();
-                let mut obj = json!({});
-                obj.as_object_mut()
-                    .unwrap()
-                    .insert(item.key.as_str().to_string(), value.clone());
-                arr.push(obj);
-                return Ok(());
-            }
+    // the trailing indices are handled together, on the array holding them
+    let keys = path_segments
+        .iter()
+        .rposition(|segment| matches!(segment, Segment::Key(_)))
+        .map_or(0, |position| position + 1);
+    let (parents, trailing) = path_segments.split_at(keys);
+    let indices: Vec<usize> = trailing
+        .iter()
+        .filter_map(|segment| match segment {
+            Segment::Index(index) => Some(*index),
+            Segment::Key(_) => None,
+        })
+        .collect();
 
-            return Err(ProcessError::Unknown {
-                message: "Unknown error".to_string(),
-            });
+    let Some((last, parents)) = parents.split_last() else {
+        if indices.is_empty() {
+            *json = match operation {
+                Operation::Delete => Value::Null,
+                _ => value.clone(),
+            };
+            return Ok(());
         }
+        return patch_array(json, path, &indices, value, operation, options);
+    };
 
-        // when we are in the last item, we need to do the actual operation
-        // we have two cases here:
-        // 1. the current item is an array
-        // 2. the current item is an object
-        if item.is_last && current.is_object() {
-            // the last item is in an array
-            // so we need to do the operation on the array
-            if item.is_array {
-                return match operation {
-                    Operation::Add => {
-                        // if the element was not in the object, we need to create it
-                        let object = current.as_object_mut().unwrap();
-                        if !object.contains_key(item.key.as_str()) {
-                            object.insert(item.key.as_str().to_string(), Value::Array(vec![]));
-                        }
-                        current = &mut current[item.key.as_str()];
-                        insert_element(
-                            current.as_array_mut().unwrap(),
-                            &item.indices,
-                            value.clone(),
-                        );
-                        Ok(())
+    // this logic is just for moving the current pointer to the right place,
+    // missing levels are created unless we are deleting
+    let mut current = json;
+    for (position, segment) in parents.iter().enumerate() {
+        let next = match path_segments[position + 1] {
+            Segment::Index(_) => json!([]),
+            Segment::Key(_) => json!({}),
+        };
+        current = match (segment, current) {
+            (Segment::Key(key), Value::Object(object)) => {
+                if !object.contains_key(key.as_ref()) {
+                    if operation == Operation::Delete {
+                        return Ok(());
                     }
-                    Operation::Change => {
-                        current = &mut current[item.key.as_str()];
-                        change_element(
-                            current.as_array_mut().unwrap(),
-                            &item.indices,
-                            value.clone(),
-                        );
-                        Ok(())
+                    object.insert(key.to_string(), next);
+                }
+                &mut object[key.as_ref()]
+            }
+            (Segment::Index(index), Value::Array(array)) => {
+                if *index >= array.len() {
+                    if operation == Operation::Delete {
+                        return Ok(());
                     }
-                    Operation::Delete => {
-                        current = &mut current[item.key.as_str()];
-                        remove_element(current.as_array_mut().unwrap(), &item.indices, options);
-                        Ok(())
+                    if *index > array.len() {
+                        return Err(invalid_path(path, "index out of bounds"));
                     }
-                };
+                    array.push(next);
+                }
+                &mut array[*index]
             }
+            _ => return Err(invalid_path(path, "it does not match the value")),
+        };
+    }
 
-            // the last item is an object
-            // so we need to do the operation on the object
-            return match operation {
-                Operation::Add => {
-                    // if the element was not in the object, we need to create it
-                    let object = current.as_object_mut().unwrap();
-                    if !object.contains_key(item.key.as_str()) {
-                        object.insert(item.key.as_str().to_string(), Value::Null);
-                    }
+    let (Segment::Key(key), Value::Object(object)) = (last, current) else {
+        return Err(invalid_path(path, "it does not match the value"));
+    };
 
-                    current[item.key.as_str()] = value.clone();
-                    Ok(())
-                }
-                Operation::Change => {
-                    current[item.key.as_str()] = value.clone();
-                    Ok(())
-                }
-                Operation::Delete => {
-                    current.as_object_mut().unwrap().remove(item.key.as_str());
-                    Ok(())
-                }
-            };
+    if !indices.is_empty() {
+        // if the array was not in the object, we need to create it
+        if !object.contains_key(key.as_ref()) {
+            if operation == Operation::Delete {
+                return Ok(());
+            }
+            object.insert(key.to_string(), json!([]));
         }
+        return patch_array(
+            &mut object[key.as_ref()],
+            path,
+            &indices,
+            value,
+            operation,
+            options,
+        );
+    }
 
-        // this logic is just for moving the current pointer to the right place
-        match current {
-            Value::Object(obj) => {
-                if item.is_array {
-                    // if the element was not in the object, we need to create it
-                    if !obj.contains_key(item.key.as_str()) {
-                        obj.insert(item.key.as_str().to_string(), json!([]));
-                    }
-
-                    current = &mut obj[item.key.as_str()];
-
-                    for (_, _index) in item.indices.iter().enumerate() {
-                        let array = current.as_array_mut().unwrap();
-
-                        if (array.is_empty() || *_index > array.len())
-                            && operation == Operation::Add
-                        {
-                            if item.indices.len() == 1 {
-                                array.push(json!({}));
-                                continue;
-                            }
+    match operation {
+        Operation::Add | Operation::Change => {
+            object.insert(key.to_string(), value.clone());
+        }
+        Operation::Delete => {
+            object.remove(key.as_ref());
+        }
+    }
 
-                            array.push(json!([]));
-                            continue;
-                        }
-                        current = &mut current[*_index];
-                    }
+    Ok(())
+}
 
-                    continue;
-                }
+/// Applies the operation to the element of `array` at the given indices
+fn patch_array(
+    array: &mut Value,
+    path: &str,
+    indices: &[usize],
+    value: &Value,
+    operation: Operation,
+    options: PatchOptions,
+) -> Result<(), ProcessError> {
+    let Value::Array(array) = array else {
+        return Err(invalid_path(path, "it does not match the value"));
+    };
 
-                // if the element was not in the object, we need to create it
-                if !obj.contains_key(item.key.as_str()) {
-                    obj.insert(item.key.as_str().to_string(), json!({}));
-                }
+    let applied = match operation {
+        Operation::Add => insert_element(array, indices, value.clone()),
+        Operation::Change => change_element(array, indices, value.clone()),
+        Operation::Delete => {
+            remove_element(array, indices, options);
+            Some(())
+        }
+    };
 
-                current = &mut obj[item.key.as_str()];
+    applied.ok_or_else(|| invalid_path(path, "index out of bounds"))
+}
 
-                continue;
-            }
-            _ => {
-                return Err(ProcessError::Unknown {
-                    message: "Unknown error".to_string(),
-                });
-            }
-        }
+fn invalid_path(path: &str, message: &str) -> ProcessError {
+    ProcessError::InvalidPath {
+        path: path.to_string(),
+        message: message.to_string(),
     }
 }
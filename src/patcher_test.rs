@@ -2,9 +2,19 @@
 mod tests {
     use crate::delta::Delta;
     use crate::delta::Operation::{Add, Change, Delete};
-    use crate::patcher::{patch, patch_by_path, PatchOptions};
+    use crate::differ::{DeltaOrder, Differ};
+    use crate::errors::ProcessError;
+    use crate::patcher::{
+        patch, patch_by_path, patch_text, try_patch, try_patch_text, PatchOptions, MAX_PATH_DEPTH,
+    };
     use serde_json::json;
-    use serde_json::Value::Null;
+    use serde_json::{Value, Value::Null};
+
+    fn round_trip(left: &Value, right: &Value, order: DeltaOrder) -> Value {
+        let mut differ = Differ::new_from_json_values(left.clone(), right.clone()).order(order);
+        let deltas = differ.diff().get_deltas();
+        patch(left.clone(), deltas, PatchOptions::default())
+    }
 
     #[test]
     fn test_patch_add_list() {
@@ -149,6 +159,8 @@ mod tests {
                 old_value: json!(Null),
                 new_value: json!(1),
                 hash: String::default(),
+                left_span: None,
+                right_span: None,
             },
             Delta {
                 operation: Add,
@@ -156,6 +168,8 @@ mod tests {
                 old_value: json!(Null),
                 new_value: json!("first name"),
                 hash: String::default(),
+                left_span: None,
+                right_span: None,
             },
             Delta {
                 operation: Change,
@@ -163,6 +177,8 @@ mod tests {
                 old_value: json!(Null),
                 new_value: json!(20),
                 hash: String::default(),
+                left_span: None,
+                right_span: None,
             },
             Delta {
                 operation: Add,
@@ -170,6 +186,8 @@ mod tests {
                 old_value: json!(Null),
                 new_value: json!(["test", "test1"]),
                 hash: String::default(),
+                left_span: None,
+                right_span: None,
             },
             Delta {
                 operation: Change,
@@ -177,6 +195,8 @@ mod tests {
                 old_value: json!(Null),
                 new_value: json!("test2"),
                 hash: String::default(),
+                left_span: None,
+                right_span: None,
             },
         ];
 
@@ -203,6 +223,8 @@ mod tests {
                 old_value: Null,
                 new_value: json!(1),
                 hash: "4437996877722456100".parse().unwrap(),
+                left_span: None,
+                right_span: None,
             },
             Delta {
                 operation: Add,
@@ -210,6 +232,8 @@ mod tests {
                 old_value: Null,
                 new_value: json!(2),
                 hash: "4437996877722456100".parse().unwrap(),
+                left_span: None,
+                right_span: None,
             },
         ];
 
@@ -230,6 +254,8 @@ mod tests {
             old_value: Null,
             new_value: json!(3),
             hash: "4437996877722456100".parse().unwrap(),
+            left_span: None,
+            right_span: None,
         }];
 
         let patched = patch(patched, &deltas, PatchOptions::default());
@@ -249,6 +275,8 @@ mod tests {
             old_value: Null,
             new_value: json!(4),
             hash: "4437996877722456100".parse().unwrap(),
+            left_span: None,
+            right_span: None,
         }];
 
         let patched = patch(patched, &deltas, PatchOptions::default());
@@ -268,6 +296,8 @@ mod tests {
             old_value: Null,
             new_value: Null,
             hash: "4437996877722456100".parse().unwrap(),
+            left_span: None,
+            right_span: None,
         }];
 
         let patched = patch(patched, &deltas, PatchOptions::default().force(false));
@@ -287,6 +317,8 @@ mod tests {
             old_value: Null,
             new_value: Null,
             hash: "4437996877722456100".parse().unwrap(),
+            left_span: None,
+            right_span: None,
         }];
 
         let patched = patch(
@@ -313,6 +345,8 @@ mod tests {
             old_value: Null,
             new_value: json!(1),
             hash: "4437996877722456100".parse().unwrap(),
+            left_span: None,
+            right_span: None,
         }];
 
         let patched = patch(
@@ -499,6 +533,17 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_patch_delete_keeps_emptied_levels() {
+        let deltas = vec![Delta::new(Delete, "$.a.b".to_string(), json!(1), Null)];
+        let patched = patch(json!({"a": {"b": 1}}), &deltas, PatchOptions::default());
+        assert_eq!(patched, json!({"a": {}}));
+
+        let deltas = vec![Delta::new(Delete, "$.a[0][0]".to_string(), json!(1), Null)];
+        let patched = patch(json!({"a": [[1]]}), &deltas, PatchOptions::default());
+        assert_eq!(patched, json!({"a": [[]]}));
+    }
+
     #[test]
     fn test_patcher_omit_empty() {
         let base = json!({
@@ -521,6 +566,8 @@ mod tests {
             old_value: json!("1"),
             new_value: Null,
             hash: "4437996877722456100".parse().unwrap(),
+            left_span: None,
+            right_span: None,
         }];
 
         let patcher = patch(
@@ -531,4 +578,280 @@ mod tests {
 
         assert_eq!(patcher, json!({}));
     }
+
+    #[test]
+    fn test_patch_array_runs() {
+        let deltas = vec![
+            Delta::new(Delete, "$.a[1]".to_string(), json!(2), Null),
+            Delta::new(Delete, "$.a[2]".to_string(), json!(3), Null),
+            Delta::new(Delete, "$.a[10]".to_string(), json!(11), Null),
+            Delta::new(Change, "$.b".to_string(), json!(1), json!(2)),
+            Delta::new(Add, "$.c[3]".to_string(), Null, json!(4)),
+            Delta::new(Add, "$.c[2]".to_string(), Null, json!(3)),
+        ];
+
+        let patched = patch(
+            json!({"a": [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11], "b": 1, "c": [1, 2]}),
+            &deltas,
+            PatchOptions::default(),
+        );
+
+        assert_eq!(
+            patched,
+            json!({"a": [1, 4, 5, 6, 7, 8, 9, 10], "b": 2, "c": [1, 2, 3, 4]})
+        );
+    }
+
+    #[test]
+    fn test_patch_keeps_order_of_related_paths() {
+        let deltas = vec![
+            Delta::new(Delete, "$.a".to_string(), json!(1), Null),
+            Delta::new(Add, "$.a".to_string(), Null, json!(5)),
+        ];
+        let patched = try_patch(json!({"a": 1}), &deltas, PatchOptions::default());
+        assert_eq!(patched.unwrap(), json!({"a": 5}));
+    }
+
+    #[test]
+    fn test_patch_deletions_before_additions() {
+        let deltas = vec![
+            Delta::new(Add, "$[0]".to_string(), Null, json!("n")),
+            Delta::new(Delete, "$[1]".to_string(), json!("y"), Null),
+        ];
+        let patched = try_patch(json!(["x", "y"]), &deltas, PatchOptions::default());
+        assert_eq!(patched.unwrap(), json!(["n", "x"]));
+    }
+
+    #[test]
+    fn test_round_trip_arrays() {
+        let cases = vec![
+            (json!({"a": [1, 2, 3]}), json!({"a": [1]})),
+            (json!({"a": [1]}), json!({"a": [1, 2, 3]})),
+            (
+                json!({"a": [{"b": 1}, {"c": [1, 2]}, [3, 4]]}),
+                json!({"a": [{"b": 2}]}),
+            ),
+            (
+                json!({"a": [[1, 2], [3]]}),
+                json!({"a": [[1], [3, 4], [5, 6]]}),
+            ),
+            (
+                json!({"a": [1, {"b": {"c": 1}}]}),
+                json!({"a": [1, {"b": {}}, []]}),
+            ),
+            (json!({"a": {"b": 1}}), json!({"a": {}})),
+            (json!({"a": {"b": {"c": 1}}, "d": 1}), json!({"d": 1})),
+            (json!([1, [2, 3], 4]), json!([1, []])),
+            (json!({}), json!({"a": [[], {}], "b": {}})),
+        ];
+
+        for (left, right) in cases {
+            for order in [DeltaOrder::Path, DeltaOrder::Document] {
+                assert_eq!(
+                    round_trip(&left, &right, order),
+                    right,
+                    "{} vs {}",
+                    left,
+                    right
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_try_patch_invalid_paths() {
+        let base = json!({"a": [1, [2]], "b": "c"});
+        let cases = vec![
+            (Add, "a.b"),
+            (Add, "$.a[5]"),
+            (Change, "$.a[5]"),
+            (Add, "$.a[1][3]"),
+            (Add, "$.a[7][0]"),
+            (Add, "$.a.b"),
+            (Add, "$.b[0]"),
+            (Change, "$.b.c"),
+            (Delete, "$.b[0]"),
+            (Add, "$[0]"),
+        ];
+
+        for (operation, path) in cases {
+            let deltas = vec![Delta::new(operation, path.to_string(), Null, json!(1))];
+            let result = try_patch(base.clone(), &deltas, PatchOptions::default());
+            assert!(
+                matches!(result, Err(ProcessError::InvalidPath { .. })),
+                "{} {:?}",
+                path,
+                result
+            );
+        }
+
+        let path = format!("${}", ".a".repeat(MAX_PATH_DEPTH + 1));
+        let deltas = vec![Delta::new(Add, path, Null, json!(1))];
+        let result = try_patch(json!({}), &deltas, PatchOptions::default());
+        assert!(matches!(result, Err(ProcessError::InvalidPath { .. })));
+    }
+
+    #[test]
+    fn test_try_patch_malformed_paths() {
+        let paths = [
+            "$.a[",
+            "$.a[]",
+            "$.a[x]",
+            "$.a[-1]",
+            "$..a",
+            "$.",
+            "$a",
+            "$['a",
+            "$['a'",
+            "$[18446744073709551616]",
+        ];
+
+        for path in paths {
+            let deltas = vec![Delta::new(Add, path.to_string(), Null, json!(1))];
+            let result = try_patch(json!({}), &deltas, PatchOptions::default());
+            assert!(
+                matches!(result, Err(ProcessError::InvalidPath { .. })),
+                "{} {:?}",
+                path,
+                result
+            );
+        }
+
+        let deltas = vec![Delta::new(Delete, "$.a[3][1]".to_string(), Null, Null)];
+        let result = try_patch(json!({"a": [[1]]}), &deltas, PatchOptions::default()).unwrap();
+        assert_eq!(result, json!({"a": [[1]]}));
+    }
+
+    fn text_deltas(left: &str, right: &Value) -> Vec<Delta> {
+        let left = crate::text::parse(left).unwrap().value();
+        let mut differ = Differ::new_from_json_values(left, right.clone());
+        differ.diff().get_deltas().clone()
+    }
+
+    #[test]
+    fn test_patch_keys_with_path_syntax() {
+        let left = json!({"": 1, "a.b": 2, "a[0]": 3, "it's": {"x.y": [1]}, "a": {"b": 4}});
+        let right = json!({"a.b": 20, "a[0]": 3, "it's": {"x.y": [1, 2]}, "a": {"b": 4}, "[]": 5});
+
+        for order in [DeltaOrder::Document, DeltaOrder::Path] {
+            assert_eq!(round_trip(&left, &right, order), right);
+            assert_eq!(round_trip(&right, &left, order), left);
+        }
+
+        let delta = Delta::new(Delete, "$['']".to_string(), json!(1), Null);
+        assert_eq!(
+            patch(json!({"": 1, "a": 2}), &[delta], PatchOptions::default()),
+            json!({"a": 2})
+        );
+    }
+
+    #[test]
+    fn test_patch_text_dotted_keys() {
+        let original = "{\n  \"editor.tabSize\": 2,\n  \"editor\": {\"tabSize\": 4}\n}\n";
+        let deltas = text_deltas(
+            original,
+            &json!({"editor.tabSize": 8, "editor": {"tabSize": 4}}),
+        );
+
+        assert_eq!(
+            patch_text(original, &deltas),
+            "{\n  \"editor.tabSize\": 8,\n  \"editor\": {\"tabSize\": 4}\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_patch_text_keeps_formatting() {
+        let original = "{\n    \"version\": 1.0,\n    \"name\":\"a\",\n    \"tags\": [\"x\"]\n}\n";
+        let deltas = text_deltas(
+            original,
+            &json!({"version": 1.0, "name": "b", "tags": ["x", "y"], "new": {"k": 1}}),
+        );
+
+        assert_eq!(
+            patch_text(original, &deltas),
+            concat!(
+                "{\n    \"version\": 1.0,\n    \"name\":\"b\",\n    \"tags\": [\"x\", \"y\"],\n",
+                "    \"new\": {\n        \"k\": 1\n    }\n}\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_patch_text_removals() {
+        let original = "{\n  \"a\": 1,\n  \"b\": 2,\n  \"c\": [1, 2, 3]\n}";
+        let deltas = text_deltas(original, &json!({"b": 2, "c": [1]}));
+        assert_eq!(
+            patch_text(original, &deltas),
+            "{\n  \"b\": 2,\n  \"c\": [1]\n}"
+        );
+
+        let deltas = text_deltas(original, &json!({"a": 1}));
+        assert_eq!(patch_text(original, &deltas), "{\n  \"a\": 1\n}");
+
+        let original = r#"{"a":1,"b":{"c":true}}"#;
+        let deltas = text_deltas(original, &json!({"b": {"c": false, "d": []}, "e": 1}));
+        assert_eq!(
+            patch_text(original, &deltas),
+            r#"{"b":{"c":false,"d":[]},"e":1}"#
+        );
+    }
+
+    #[test]
+    fn test_patch_text_comments() {
+        let original = "{\n  // the port\n  \"port\": 80, /* http */\n  \"hosts\": [\"a\",],\n}\n";
+        let deltas = text_deltas(original, &json!({"port": 8080, "hosts": []}));
+
+        assert_eq!(
+            patch_text(original, &deltas),
+            "{\n  // the port\n  \"port\": 8080, /* http */\n  \"hosts\": [],\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_patch_text_trailing_comments() {
+        let original = "{\n  \"a\": 1, // first\n  \"b\": 2 // second\n}\n";
+
+        let deltas = text_deltas(original, &json!({"a": 1, "b": 2, "c": 3}));
+        assert_eq!(
+            patch_text(original, &deltas),
+            "{\n  \"a\": 1, // first\n  \"b\": 2, // second\n  \"c\": 3\n}\n"
+        );
+
+        let deltas = text_deltas(original, &json!({"a": 1}));
+        assert_eq!(patch_text(original, &deltas), "{\n  \"a\": 1 // first\n}\n");
+
+        let deltas = text_deltas(original, &json!({"a": 1, "c": 3}));
+        assert_eq!(
+            patch_text(original, &deltas),
+            "{\n  \"a\": 1, // first\n  \"c\": 3\n}\n"
+        );
+
+        let original = "[\n  1 /* one */\n]";
+        let deltas = text_deltas(original, &json!([1, 2]));
+        assert_eq!(patch_text(original, &deltas), "[\n  1, /* one */\n  2\n]");
+    }
+
+    #[test]
+    fn test_patch_text_matches_patch() {
+        let original = "[\n  {\"a\": [1, {\"b\": null}]},\n  2,\n  \"s\"\n]";
+        let right = json!([{"a": [1, {"b": [1, 2]}, 3], "c": "d"}, {"e": 1}]);
+        let deltas = text_deltas(original, &right);
+
+        let patched = patch_text(original, &deltas);
+        assert_eq!(serde_json::from_str::<Value>(&patched).unwrap(), right);
+    }
+
+    #[test]
+    fn test_try_patch_text_errors() {
+        assert!(matches!(
+            try_patch_text("{\"a\": }", &[]),
+            Err(ProcessError::InvalidJson { offset: 6, .. })
+        ));
+
+        let delta = Delta::new(Change, "$.a.c".to_string(), Null, json!(1));
+        assert!(matches!(
+            try_patch_text("{\"a\": 1}", &[delta]),
+            Err(ProcessError::InvalidPath { .. })
+        ));
+    }
 }
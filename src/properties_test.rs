@@ -0,0 +1,117 @@
+#[cfg(test)]
+mod tests {
+    use crate::compose::compose;
+    use crate::delta::{invert, Delta};
+    use crate::differ::{DeltaOrder, Differ};
+    use crate::merge::{merge, Strategy};
+    use crate::patcher::{patch, patch_text, PatchOptions};
+    use crate::strategies::{json_chain, json_pair, json_triple, json_value};
+    use crate::transform::transform;
+    use proptest::prelude::*;
+    use serde_json::Value;
+
+    fn diff(left: &Value, right: &Value, order: DeltaOrder) -> Vec<Delta> {
+        let mut differ = Differ::new_from_json_values(left.clone(), right.clone()).order(order);
+        differ.diff().get_deltas().clone()
+    }
+
+    proptest! {
+        #[test]
+        fn patch_diff_round_trips(left in json_value(), right in json_value()) {
+            for order in [DeltaOrder::Path, DeltaOrder::Document] {
+                let deltas = diff(&left, &right, order);
+                prop_assert_eq!(patch(left.clone(), &deltas, PatchOptions::default()), right.clone());
+            }
+        }
+
+        #[test]
+        fn patch_diff_round_trips_close_values((left, right) in json_pair()) {
+            for order in [DeltaOrder::Path, DeltaOrder::Document] {
+                let deltas = diff(&left, &right, order);
+                prop_assert_eq!(patch(left.clone(), &deltas, PatchOptions::default()), right.clone());
+            }
+        }
+
+        #[test]
+        fn patched_text_reads_as_patched_value((left, right) in json_pair()) {
+            let deltas = diff(&left, &right, DeltaOrder::Path);
+            // floats are compared after the same trip through text
+            let expected: Value = serde_json::from_str(&right.to_string()).unwrap();
+            for text in [left.to_string(), serde_json::to_string_pretty(&left).unwrap()] {
+                let patched = patch_text(&text, &deltas);
+                prop_assert_eq!(serde_json::from_str::<Value>(&patched).unwrap(), expected.clone());
+            }
+        }
+
+        #[test]
+        fn inverted_diff_restores_left((left, right) in json_pair()) {
+            for order in [DeltaOrder::Path, DeltaOrder::Document] {
+                let deltas = invert(&diff(&left, &right, order));
+                prop_assert_eq!(patch(right.clone(), &deltas, PatchOptions::default()), left.clone());
+            }
+        }
+
+        #[test]
+        fn diff_with_itself_is_empty(value in json_value()) {
+            let mut differ = Differ::new_from_json_values(value.clone(), value);
+            prop_assert!(differ.is_equal());
+            prop_assert!(!differ.diff().has_changes());
+        }
+
+        #[test]
+        fn hashes_are_deterministic((left, right) in json_pair()) {
+            let deltas = diff(&left, &right, DeltaOrder::Path);
+            prop_assert_eq!(&deltas, &diff(&left, &right, DeltaOrder::Path));
+
+            for delta in &deltas {
+                let rebuilt = Delta::new(
+                    delta.operation.clone(),
+                    delta.path.clone(),
+                    delta.old_value.clone(),
+                    delta.new_value.clone(),
+                );
+                prop_assert_eq!(&rebuilt.hash, &delta.hash);
+                prop_assert_eq!(&delta.invert().invert(), delta);
+            }
+        }
+
+        #[test]
+        fn merge_with_one_side_unchanged((base, other) in json_pair()) {
+            prop_assert_eq!(merge(&base, &other, &base).merged(), Some(other.clone()));
+            prop_assert_eq!(merge(&base, &base, &other).merged(), Some(other.clone()));
+            prop_assert_eq!(merge(&base, &other, &other).merged(), Some(other));
+        }
+
+        #[test]
+        fn merge_is_symmetric((base, ours, theirs) in json_triple()) {
+            let result = merge(&base, &ours, &theirs);
+            let swapped = merge(&base, &theirs, &ours);
+
+            prop_assert_eq!(result.conflicts().len(), swapped.conflicts().len());
+            prop_assert_eq!(result.resolve(Strategy::Ours), swapped.resolve(Strategy::Theirs));
+            prop_assert_eq!(result.resolve(Strategy::Theirs), swapped.resolve(Strategy::Ours));
+        }
+
+        #[test]
+        fn compose_equals_applying_in_sequence((a, b, c) in json_chain()) {
+            let first = diff(&a, &b, DeltaOrder::Path);
+            let second = diff(&b, &c, DeltaOrder::Path);
+            let composed = compose(&first, &second);
+
+            prop_assert_eq!(patch(a.clone(), &composed, PatchOptions::default()), c.clone());
+            prop_assert_eq!(patch(c, &invert(&composed), PatchOptions::default()), a);
+        }
+
+        #[test]
+        fn transformed_lists_converge((base, ours, theirs) in json_triple()) {
+            let a = diff(&base, &ours, DeltaOrder::Path);
+            let b = diff(&base, &theirs, DeltaOrder::Path);
+            let transformed = transform(&a, &b);
+
+            let options = PatchOptions::default();
+            let a_first = patch(ours, &transformed.b, options);
+            let b_first = patch(theirs, &transformed.a, options);
+            prop_assert_eq!(a_first, b_first);
+        }
+    }
+}
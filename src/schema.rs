@@ -0,0 +1,697 @@
+//! JSON Schema aware diffing.
+//!
+//! Only a subset of JSON Schema is understood: `type`, `enum`, `properties`,
+//! `required`, `additionalProperties` and `items`, along with the `true` and
+//! `false` schemas. Other keywords are ignored, and `$ref` is only followed
+//! when comparing the schemas of an OpenAPI document.
+//!
+//! `classify` labels the deltas between two documents against a schema, and
+//! `diff_schemas` lists the changes between two versions of a schema, each
+//! one breaking or not for the consumers of the documents.
+
+use crate::delta::{Delta, Operation};
+use crate::errors::ProcessError;
+use crate::utils::{get, path_prefixes, push_index, push_key, segments, Segment};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+
+/// A parsed JSON Schema, see the module documentation for what is supported
+#[derive(Debug, PartialEq, Clone)]
+pub struct Schema {
+    /// False for the `false` schema, which accepts nothing
+    accepts: bool,
+    /// The types allowed, `None` allows any
+    types: Option<BTreeSet<String>>,
+    enumeration: Option<Vec<Value>>,
+    properties: BTreeMap<String, Schema>,
+    required: BTreeSet<String>,
+    /// The schema of the properties not in `properties`, `None` allows any
+    additional: Option<Box<Schema>>,
+    items: Option<Box<Schema>>,
+    /// A `$ref`, only followed by `diff_referenced_schemas`
+    reference: Option<String>,
+}
+
+/// The schemas `$ref`s point to, by reference
+pub(crate) type References = HashMap<String, Schema>;
+
+/// The `true` schema, which accepts anything
+static ANY: Schema = Schema::new(true);
+
+const TYPES: [&str; 7] = [
+    "array", "boolean", "integer", "null", "number", "object", "string",
+];
+
+impl Schema {
+    /// Parses a schema from its JSON value
+    pub fn from_value(value: &Value) -> Result<Schema, ProcessError> {
+        let object = match value {
+            Value::Bool(accepts) => return Ok(Schema::new(*accepts)),
+            Value::Object(object) => object,
+            value => return Err(invalid(format!("{} is not a schema", value))),
+        };
+
+        let types = match object.get("type") {
+            None => None,
+            Some(Value::String(name)) => Some(BTreeSet::from([type_name(name)?])),
+            Some(Value::Array(names)) => Some(
+                names
+                    .iter()
+                    .map(|name| match name {
+                        Value::String(name) => type_name(name),
+                        name => Err(invalid(format!("{} is not a type", name))),
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            Some(value) => return Err(invalid(format!("{} is not a type", value))),
+        };
+
+        let enumeration = match object.get("enum") {
+            None => None,
+            Some(Value::Array(values)) => Some(values.clone()),
+            Some(value) => return Err(invalid(format!("enum {} is not an array", value))),
+        };
+
+        let properties = match object.get("properties") {
+            None => BTreeMap::new(),
+            Some(Value::Object(properties)) => properties
+                .iter()
+                .map(|(name, schema)| Ok((name.clone(), Schema::from_value(schema)?)))
+                .collect::<Result<_, ProcessError>>()?,
+            Some(value) => return Err(invalid(format!("properties {} is not an object", value))),
+        };
+
+        let required = match object.get("required") {
+            None => BTreeSet::new(),
+            Some(Value::Array(names)) => names
+                .iter()
+                .map(|name| match name {
+                    Value::String(name) => Ok(name.clone()),
+                    name => Err(invalid(format!("required {} is not a string", name))),
+                })
+                .collect::<Result<_, _>>()?,
+            Some(value) => return Err(invalid(format!("required {} is not an array", value))),
+        };
+
+        Ok(Schema {
+            accepts: true,
+            types,
+            enumeration,
+            properties,
+            required,
+            additional: subschema(object, "additionalProperties")?,
+            items: subschema(object, "items")?,
+            reference: object.get("$ref").and_then(Value::as_str).map(String::from),
+        })
+    }
+
+    /// The `true` or `false` schema
+    const fn new(accepts: bool) -> Schema {
+        Schema {
+            accepts,
+            types: None,
+            enumeration: None,
+            properties: BTreeMap::new(),
+            required: BTreeSet::new(),
+            additional: None,
+            items: None,
+            reference: None,
+        }
+    }
+
+    /// Checks a value against the schema, the error names the first value
+    /// that does not match
+    pub fn validate(&self, value: &Value) -> Result<(), String> {
+        self.validate_at(value, &mut "$".to_string())
+    }
+
+    fn validate_at(&self, value: &Value, path: &mut String) -> Result<(), String> {
+        if !self.accepts {
+            return Err(format!("{} is not allowed", path));
+        }
+        if let Some(types) = &self.types {
+            if !types.iter().any(|name| has_type(value, name)) {
+                let types: Vec<&str> = types.iter().map(String::as_str).collect();
+                return Err(format!("{} is not of type {}", path, types.join(" or ")));
+            }
+        }
+        if let Some(values) = &self.enumeration {
+            if !values.contains(value) {
+                return Err(format!("{} is not one of the enum values", path));
+            }
+        }
+
+        let length = path.len();
+        match value {
+            Value::Object(object) => {
+                self.validate_required(object, path)?;
+                for (name, value) in object {
+                    push_key(path, name);
+                    if let Some(schema) = self.property(name) {
+                        schema.validate_at(value, path)?;
+                    }
+                    path.truncate(length);
+                }
+            }
+            Value::Array(elements) => {
+                if let Some(items) = &self.items {
+                    for (index, element) in elements.iter().enumerate() {
+                        push_index(path, index);
+                        items.validate_at(element, path)?;
+                        path.truncate(length);
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Checks that an object has the required properties
+    fn validate_required(&self, object: &Map<String, Value>, path: &str) -> Result<(), String> {
+        match self
+            .required
+            .iter()
+            .find(|name| !object.contains_key(*name))
+        {
+            Some(name) => Err(format!(
+                "{} is missing the required property {}",
+                path, name
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the schema of a property, `None` when any value is allowed
+    fn property(&self, name: &str) -> Option<&Schema> {
+        self.properties.get(name).or(self.additional.as_deref())
+    }
+
+    /// Returns the schema of the value at the given path segments, `None`
+    /// when any value is allowed
+    fn at<'s>(&self, path: impl IntoIterator<Item = Segment<'s>>) -> Option<&Schema> {
+        path.into_iter()
+            .try_fold(self, |schema, segment| match segment {
+                Segment::Key(name) => schema.property(&name),
+                Segment::Index(_) => schema.items.as_deref(),
+            })
+    }
+
+    /// Returns the `$ref`s of the schema and of its subschemas
+    pub(crate) fn references(&self) -> Vec<&str> {
+        let mut references: Vec<&str> = self.reference.as_deref().into_iter().collect();
+        let subschemas = self
+            .properties
+            .values()
+            .chain(self.additional.as_deref())
+            .chain(self.items.as_deref());
+        for schema in subschemas {
+            references.extend(schema.references());
+        }
+        references
+    }
+
+    /// Returns true when the properties not listed are rejected
+    fn closed(&self) -> bool {
+        self.additional
+            .as_ref()
+            .is_some_and(|schema| !schema.accepts)
+    }
+}
+
+/// Whether a change breaks the consumers of the documents
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum Severity {
+    Breaking,
+    NonBreaking,
+}
+
+/// What a delta between two documents does, according to a schema
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum DeltaKind {
+    /// A property the schema requires was removed
+    RequiredRemoved,
+    /// The new value does not match the schema
+    Violation { message: String },
+    /// The value changed to another type, which the schema allows
+    TypeChanged,
+    /// The new value matches the schema
+    Valid,
+}
+
+/// A delta along with its kind and severity
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ClassifiedDelta {
+    pub delta: Delta,
+    pub kind: DeltaKind,
+    pub severity: Severity,
+}
+
+/// Classifies the deltas between two documents, the `right` one being
+/// expected to match the schema. Removed required properties, violations and
+/// type changes are breaking.
+///
+/// A deletion of, or nested in, a required property the right document no
+/// longer has is reported as `RequiredRemoved`, and an addition leaving its
+/// object without a required property as a `Violation`.
+pub fn classify(schema: &Schema, right: &Value, deltas: &[Delta]) -> Vec<ClassifiedDelta> {
+    deltas
+        .iter()
+        .map(|delta| {
+            let kind = classify_delta(schema, right, delta);
+            let severity = match kind {
+                DeltaKind::Valid => Severity::NonBreaking,
+                _ => Severity::Breaking,
+            };
+            ClassifiedDelta {
+                delta: delta.clone(),
+                kind,
+                severity,
+            }
+        })
+        .collect()
+}
+
+fn classify_delta(schema: &Schema, right: &Value, delta: &Delta) -> DeltaKind {
+    let path: Vec<Segment> = segments(&delta.path).collect();
+
+    if delta.operation == Operation::Delete {
+        // the first level missing from the right document is the one removed
+        let missing =
+            (0..path.len()).find(|depth| get(right, path[..=*depth].iter().cloned()).is_none());
+        let required = match missing.map(|depth| path.split_at(depth)) {
+            Some((parents, [Segment::Key(name), ..])) => schema
+                .at(parents.iter().cloned())
+                .is_some_and(|parent| parent.required.contains(name.as_ref())),
+            _ => false,
+        };
+        return if required {
+            DeltaKind::RequiredRemoved
+        } else {
+            DeltaKind::Valid
+        };
+    }
+
+    let result = match schema.at(path.iter().cloned()) {
+        Some(schema) => schema.validate_at(&delta.new_value, &mut delta.path.clone()),
+        None => Ok(()),
+    };
+    let result = match delta.operation {
+        Operation::Add => result.and_then(|()| enclosing_required(schema, right, delta, &path)),
+        _ => result,
+    };
+    match result {
+        Err(message) => DeltaKind::Violation { message },
+        _ if is_type_change(delta) => DeltaKind::TypeChanged,
+        _ => DeltaKind::Valid,
+    }
+}
+
+/// Checks the required properties of the object holding an added property
+fn enclosing_required(
+    schema: &Schema,
+    right: &Value,
+    delta: &Delta,
+    path: &[Segment],
+) -> Result<(), String> {
+    let Some((Segment::Key(_), parents)) = path.split_last() else {
+        return Ok(());
+    };
+    let (Some(parent), Some(Value::Object(object))) = (
+        schema.at(parents.iter().cloned()),
+        get(right, parents.iter().cloned()),
+    ) else {
+        return Ok(());
+    };
+
+    let parent_path = path_prefixes(&delta.path)
+        .filter(|prefix| prefix.len() < delta.path.len())
+        .last()
+        .unwrap_or("$");
+    parent.validate_required(object, parent_path)
+}
+
+fn is_type_change(delta: &Delta) -> bool {
+    delta.operation == Operation::Change && kind(&delta.old_value) != kind(&delta.new_value)
+}
+
+/// How the documents described by a schema are used by its consumers
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum Usage {
+    /// Consumers read the documents, as with API responses. Changes that
+    /// remove values or allow new ones are breaking.
+    Read,
+    /// Consumers write the documents, as with API requests. Changes that
+    /// reject documents the old schema accepted are breaking.
+    Write,
+}
+
+/// What changed between two versions of a schema
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum SchemaChangeKind {
+    /// `None` stands for any type
+    TypeChanged {
+        old: Option<BTreeSet<String>>,
+        new: Option<BTreeSet<String>>,
+    },
+    PropertyAdded,
+    PropertyRemoved,
+    RequiredAdded,
+    RequiredRemoved,
+    EnumValueAdded {
+        value: Value,
+    },
+    EnumValueRemoved {
+        value: Value,
+    },
+    /// The enum was added or removed, `None` stands for any value
+    EnumChanged {
+        old: Option<Vec<Value>>,
+        new: Option<Vec<Value>>,
+    },
+    AdditionalPropertiesRejected,
+    AdditionalPropertiesAllowed,
+}
+
+/// A change between two versions of a schema. The path is the one of the
+/// values it applies to in the documents, with `[*]` for array elements and
+/// `.*` for the properties not listed in `properties`.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct SchemaChange {
+    pub path: String,
+    pub kind: SchemaChangeKind,
+    pub severity: Severity,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Breaking => write!(f, "breaking"),
+            Severity::NonBreaking => write!(f, "non-breaking"),
+        }
+    }
+}
+
+impl fmt::Display for SchemaChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let types = |types: &Option<BTreeSet<String>>| match types {
+            Some(types) => types.iter().cloned().collect::<Vec<_>>().join(" or "),
+            None => "any".to_string(),
+        };
+
+        match self {
+            SchemaChangeKind::TypeChanged { old, new } => {
+                write!(f, "type changed from {} to {}", types(old), types(new))
+            }
+            SchemaChangeKind::PropertyAdded => write!(f, "property added"),
+            SchemaChangeKind::PropertyRemoved => write!(f, "property removed"),
+            SchemaChangeKind::RequiredAdded => write!(f, "now required"),
+            SchemaChangeKind::RequiredRemoved => write!(f, "no longer required"),
+            SchemaChangeKind::EnumValueAdded { value } => write!(f, "enum value {} added", value),
+            SchemaChangeKind::EnumValueRemoved { value } => {
+                write!(f, "enum value {} removed", value)
+            }
+            SchemaChangeKind::EnumChanged { new: Some(_), .. } => {
+                write!(f, "restricted to enum values")
+            }
+            SchemaChangeKind::EnumChanged { new: None, .. } => {
+                write!(f, "no longer restricted to enum values")
+            }
+            SchemaChangeKind::AdditionalPropertiesRejected => {
+                write!(f, "additional properties rejected")
+            }
+            SchemaChangeKind::AdditionalPropertiesAllowed => {
+                write!(f, "additional properties allowed")
+            }
+        }
+    }
+}
+
+impl fmt::Display for SchemaChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} ({})", self.path, self.kind, self.severity)
+    }
+}
+
+/// Lists the changes between two versions of a schema, labeled as breaking
+/// or not for consumers using the documents as `usage` says
+pub fn diff_schemas(old: &Schema, new: &Schema, usage: Usage) -> Vec<SchemaChange> {
+    diff_referenced_schemas(old, new, usage, &References::new(), &References::new())
+}
+
+/// Same as `diff_schemas`, following the `$ref`s of each version to the
+/// schemas they point to. A pair of schemas reached again through a `$ref`
+/// is only compared once, its changes being listed at the first path.
+pub(crate) fn diff_referenced_schemas(
+    old: &Schema,
+    new: &Schema,
+    usage: Usage,
+    old_references: &References,
+    new_references: &References,
+) -> Vec<SchemaChange> {
+    let mut comparison = Comparison {
+        usage,
+        old_references,
+        new_references,
+        compared: HashSet::new(),
+        changes: Vec::new(),
+    };
+    compare(old, new, &mut "$".to_string(), &mut comparison);
+    comparison.changes
+}
+
+/// State of `diff_referenced_schemas`
+struct Comparison<'s> {
+    usage: Usage,
+    old_references: &'s References,
+    new_references: &'s References,
+    /// The pairs of schemas compared after following a `$ref`
+    compared: HashSet<(*const Schema, *const Schema)>,
+    changes: Vec<SchemaChange>,
+}
+
+fn compare<'s>(
+    old: &'s Schema,
+    new: &'s Schema,
+    path: &mut String,
+    comparison: &mut Comparison<'s>,
+) {
+    let (old, old_followed) = follow(old, comparison.old_references);
+    let (new, new_followed) = follow(new, comparison.new_references);
+    if (old_followed || new_followed) && !comparison.compared.insert((old, new)) {
+        return;
+    }
+
+    let usage = comparison.usage;
+    let changes = &mut comparison.changes;
+    let mut push = |path: &str, kind: SchemaChangeKind, breaking: bool| {
+        let severity = if breaking {
+            Severity::Breaking
+        } else {
+            Severity::NonBreaking
+        };
+        changes.push(SchemaChange {
+            path: path.to_string(),
+            kind,
+            severity,
+        });
+    };
+    let (read, write) = (usage == Usage::Read, usage == Usage::Write);
+
+    let (old_types, new_types) = (accepted_types(old), accepted_types(new));
+    if old_types != new_types {
+        // readers break on new types, writers on the types no longer allowed
+        let added = !is_subset(&new_types, &old_types);
+        let removed = !is_subset(&old_types, &new_types);
+        push(
+            path,
+            SchemaChangeKind::TypeChanged {
+                old: old_types,
+                new: new_types,
+            },
+            (read && added) || (write && removed),
+        );
+    }
+
+    match (&old.enumeration, &new.enumeration) {
+        (Some(old_values), Some(new_values)) => {
+            for value in old_values
+                .iter()
+                .filter(|value| !new_values.contains(value))
+            {
+                let kind = SchemaChangeKind::EnumValueRemoved {
+                    value: value.clone(),
+                };
+                push(path, kind, write);
+            }
+            for value in new_values
+                .iter()
+                .filter(|value| !old_values.contains(value))
+            {
+                let kind = SchemaChangeKind::EnumValueAdded {
+                    value: value.clone(),
+                };
+                push(path, kind, read);
+            }
+        }
+        (None, None) => {}
+        (old_values, new_values) => {
+            let kind = SchemaChangeKind::EnumChanged {
+                old: old_values.clone(),
+                new: new_values.clone(),
+            };
+            push(path, kind, if new_values.is_some() { write } else { read });
+        }
+    }
+
+    match (old.closed(), new.closed()) {
+        (false, true) => push(path, SchemaChangeKind::AdditionalPropertiesRejected, write),
+        (true, false) => push(path, SchemaChangeKind::AdditionalPropertiesAllowed, false),
+        _ => {}
+    }
+
+    let length = path.len();
+    let names: BTreeSet<&String> = old
+        .properties
+        .keys()
+        .chain(new.properties.keys())
+        .chain(old.required.iter())
+        .chain(new.required.iter())
+        .collect();
+    for name in names {
+        push_key(path, name);
+
+        match (old.properties.get(name), new.properties.get(name)) {
+            (Some(_), None) => push(
+                path,
+                SchemaChangeKind::PropertyRemoved,
+                read || (write && new.closed()),
+            ),
+            (None, Some(_)) => push(path, SchemaChangeKind::PropertyAdded, false),
+            _ => {}
+        }
+        match (old.required.contains(name), new.required.contains(name)) {
+            (false, true) => push(path, SchemaChangeKind::RequiredAdded, write),
+            (true, false) => push(path, SchemaChangeKind::RequiredRemoved, read),
+            _ => {}
+        }
+
+        path.truncate(length);
+    }
+
+    for name in old.properties.keys() {
+        if let (Some(old), Some(new)) = (old.properties.get(name), new.properties.get(name)) {
+            push_key(path, name);
+            compare(old, new, path, comparison);
+            path.truncate(length);
+        }
+    }
+
+    // a missing subschema allows any value, as the `true` schema does, and
+    // rejecting additional properties was reported above
+    let subschemas = [
+        (".*", &old.additional, &new.additional),
+        ("[*]", &old.items, &new.items),
+    ];
+    for (suffix, old_schema, new_schema) in subschemas {
+        let closed = suffix == ".*" && (old.closed() || new.closed());
+        if closed || (old_schema.is_none() && new_schema.is_none()) {
+            continue;
+        }
+
+        path.push_str(suffix);
+        compare(
+            old_schema.as_deref().unwrap_or(&ANY),
+            new_schema.as_deref().unwrap_or(&ANY),
+            path,
+            comparison,
+        );
+        path.truncate(length);
+    }
+}
+
+/// Follows the `$ref`s of a schema to the schema they point to, a cycle of
+/// references allowing any value. Also returns whether one was followed.
+fn follow<'s>(mut schema: &'s Schema, references: &'s References) -> (&'s Schema, bool) {
+    let mut followed = 0;
+    while let Some(target) = schema
+        .reference
+        .as_ref()
+        .and_then(|reference| references.get(reference))
+    {
+        if followed == references.len() {
+            return (&ANY, true);
+        }
+        followed += 1;
+        schema = target;
+    }
+    (schema, followed > 0)
+}
+
+/// Returns the types a schema accepts, `None` for any
+fn accepted_types(schema: &Schema) -> Option<BTreeSet<String>> {
+    if !schema.accepts {
+        return Some(BTreeSet::new());
+    }
+    schema.types.clone()
+}
+
+/// Returns true if every type of `types` is in `of`, `None` being any type
+fn is_subset(types: &Option<BTreeSet<String>>, of: &Option<BTreeSet<String>>) -> bool {
+    match (types, of) {
+        (_, None) => true,
+        (None, Some(_)) => false,
+        (Some(types), Some(of)) => types
+            .iter()
+            .all(|name| of.contains(name) || (name == "integer" && of.contains("number"))),
+    }
+}
+
+fn subschema(
+    object: &Map<String, Value>,
+    keyword: &str,
+) -> Result<Option<Box<Schema>>, ProcessError> {
+    object
+        .get(keyword)
+        .map(|value| Schema::from_value(value).map(Box::new))
+        .transpose()
+}
+
+fn type_name(name: &str) -> Result<String, ProcessError> {
+    if TYPES.contains(&name) {
+        Ok(name.to_string())
+    } else {
+        Err(invalid(format!("unknown type {}", name)))
+    }
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match value {
+        Value::Number(number) if name == "integer" => {
+            number.is_i64() || number.is_u64() || number.as_f64().is_some_and(|n| n.fract() == 0.0)
+        }
+        value => kind(value) == name,
+    }
+}
+
+/// Returns the JSON Schema type of a value, integers being numbers
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn invalid(message: String) -> ProcessError {
+    ProcessError::InvalidDocument {
+        format: "JSON Schema".to_string(),
+        message,
+    }
+}
@@ -0,0 +1,326 @@
+#[cfg(test)]
+mod tests {
+    use crate::differ::Differ;
+    use crate::errors::ProcessError;
+    use crate::schema::{
+        classify, diff_schemas, DeltaKind, Schema, SchemaChange, SchemaChangeKind, Severity, Usage,
+    };
+    use serde_json::{json, Value};
+    use std::collections::BTreeSet;
+
+    fn schema(value: Value) -> Schema {
+        Schema::from_value(&value).unwrap()
+    }
+
+    fn user_schema() -> Schema {
+        schema(json!({
+            "type": "object",
+            "required": ["id", "name"],
+            "additionalProperties": false,
+            "properties": {
+                "id": {"type": "integer"},
+                "name": {"type": "string"},
+                "nickname": {"type": ["string", "null"]},
+                "role": {"enum": ["admin", "user"]},
+                "tags": {"type": "array", "items": {"type": "string"}}
+            }
+        }))
+    }
+
+    #[test]
+    fn validate_documents() {
+        let schema = user_schema();
+
+        assert_eq!(schema.validate(&json!({"id": 1, "name": "a"})), Ok(()));
+        assert_eq!(
+            schema.validate(&json!({"id": 1.5, "name": "a"})),
+            Err("$.id is not of type integer".to_string())
+        );
+        assert_eq!(
+            schema.validate(&json!({"id": 1})),
+            Err("$ is missing the required property name".to_string())
+        );
+        assert_eq!(
+            schema.validate(&json!({"id": 1, "name": "a", "tags": ["x", 2]})),
+            Err("$.tags[1] is not of type string".to_string())
+        );
+        assert_eq!(
+            schema.validate(&json!({"id": 1, "name": "a", "extra": true})),
+            Err("$.extra is not allowed".to_string())
+        );
+        assert!(Schema::from_value(&json!(true))
+            .unwrap()
+            .validate(&json!([1]))
+            .is_ok());
+    }
+
+    #[test]
+    fn invalid_schemas() {
+        for value in [
+            json!(1),
+            json!({"type": "text"}),
+            json!({"type": [1]}),
+            json!({"required": "id"}),
+            json!({"properties": {"a": 2}}),
+            json!({"items": "string"}),
+        ] {
+            assert!(
+                matches!(
+                    Schema::from_value(&value),
+                    Err(ProcessError::InvalidDocument { .. })
+                ),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn classify_deltas() {
+        let left = json!({"id": 1, "name": "a", "nickname": "b", "role": "user", "tags": ["x"]});
+        let right = json!({"id": 1, "nickname": null, "role": "owner", "tags": ["x", 2], "age": 3});
+
+        let mut differ = Differ::new_from_json_values(left, right);
+        differ.diff();
+        let kinds: Vec<(String, DeltaKind, Severity)> = differ
+            .classify(&user_schema())
+            .into_iter()
+            .map(|classified| (classified.delta.path, classified.kind, classified.severity))
+            .collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                (
+                    "$.age".to_string(),
+                    DeltaKind::Violation {
+                        message: "$.age is not allowed".to_string()
+                    },
+                    Severity::Breaking
+                ),
+                (
+                    "$.name".to_string(),
+                    DeltaKind::RequiredRemoved,
+                    Severity::Breaking
+                ),
+                (
+                    "$.nickname".to_string(),
+                    DeltaKind::TypeChanged,
+                    Severity::Breaking
+                ),
+                (
+                    "$.role".to_string(),
+                    DeltaKind::Violation {
+                        message: "$.role is not one of the enum values".to_string()
+                    },
+                    Severity::Breaking
+                ),
+                (
+                    "$.tags[1]".to_string(),
+                    DeltaKind::Violation {
+                        message: "$.tags[1] is not of type string".to_string()
+                    },
+                    Severity::Breaking
+                ),
+            ]
+        );
+
+        let right = json!({"id": 2, "name": "a", "tags": []});
+        let mut differ = Differ::new_from_json_values(json!({"id": 1, "name": "a"}), right.clone());
+        differ.diff();
+        let classified = classify(&user_schema(), &right, differ.get_deltas());
+        assert!(classified
+            .iter()
+            .all(|classified| classified.kind == DeltaKind::Valid
+                && classified.severity == Severity::NonBreaking));
+    }
+
+    #[test]
+    fn classify_nested_required_properties() {
+        let schema = schema(json!({
+            "required": ["tags", "address"],
+            "properties": {
+                "address": {
+                    "required": ["city", "zip"],
+                    "properties": {"city": {"type": "string"}, "zip": {"type": "string"}}
+                }
+            }
+        }));
+        let classify = |left: Value, right: Value| -> Vec<(String, DeltaKind)> {
+            let mut differ = Differ::new_from_json_values(left, right);
+            differ.diff();
+            differ
+                .classify(&schema)
+                .into_iter()
+                .map(|classified| (classified.delta.path, classified.kind))
+                .collect()
+        };
+
+        let left = json!({"tags": ["a"], "address": {"city": "x", "zip": "1"}});
+        assert_eq!(
+            classify(left.clone(), json!({})),
+            vec![
+                ("$.address".to_string(), DeltaKind::RequiredRemoved),
+                ("$.tags".to_string(), DeltaKind::RequiredRemoved),
+            ]
+        );
+
+        assert_eq!(
+            classify(left, json!({"tags": ["a"], "address": {"city": "x"}})),
+            vec![("$.address.zip".to_string(), DeltaKind::RequiredRemoved)]
+        );
+
+        assert_eq!(
+            classify(
+                json!({"tags": []}),
+                json!({"tags": [], "address": {"zip": "1"}})
+            ),
+            vec![(
+                "$.address".to_string(),
+                DeltaKind::Violation {
+                    message: "$.address is missing the required property city".to_string()
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn diff_schema_versions() {
+        let old = user_schema();
+        let new = schema(json!({
+            "type": "object",
+            "required": ["id", "email"],
+            "properties": {
+                "id": {"type": "string"},
+                "name": {"type": "string"},
+                "email": {"type": "string"},
+                "role": {"enum": ["admin", "user", "guest"]},
+                "tags": {"type": "array", "items": {"type": "string", "enum": ["a", "b"]}}
+            }
+        }));
+
+        let changes: Vec<String> = diff_schemas(&old, &new, Usage::Read)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                "$: additional properties allowed (non-breaking)",
+                "$.email: property added (non-breaking)",
+                "$.email: now required (non-breaking)",
+                "$.name: no longer required (breaking)",
+                "$.nickname: property removed (breaking)",
+                "$.id: type changed from integer to string (breaking)",
+                "$.role: enum value \"guest\" added (breaking)",
+                "$.tags[*]: restricted to enum values (non-breaking)",
+            ]
+        );
+
+        let breaking: Vec<(String, SchemaChangeKind)> = diff_schemas(&old, &new, Usage::Write)
+            .into_iter()
+            .filter(|change| change.severity == Severity::Breaking)
+            .map(|change| (change.path, change.kind))
+            .collect();
+        assert_eq!(
+            breaking,
+            vec![
+                ("$.email".to_string(), SchemaChangeKind::RequiredAdded),
+                (
+                    "$.id".to_string(),
+                    SchemaChangeKind::TypeChanged {
+                        old: Some(["integer".to_string()].into()),
+                        new: Some(["string".to_string()].into()),
+                    }
+                ),
+                (
+                    "$.tags[*]".to_string(),
+                    SchemaChangeKind::EnumChanged {
+                        old: None,
+                        new: Some(vec![json!("a"), json!("b")]),
+                    }
+                ),
+            ]
+        );
+
+        assert!(diff_schemas(&old, &old, Usage::Write).is_empty());
+    }
+
+    #[test]
+    fn diff_schema_types() {
+        let old = schema(json!({"type": "integer"}));
+        let wider = schema(json!({"type": ["number", "null"]}));
+
+        let severities = |old: &Schema, new: &Schema| {
+            (
+                diff_schemas(old, new, Usage::Read)[0].severity,
+                diff_schemas(old, new, Usage::Write)[0].severity,
+            )
+        };
+        assert_eq!(
+            severities(&old, &wider),
+            (Severity::Breaking, Severity::NonBreaking)
+        );
+        assert_eq!(
+            severities(&wider, &old),
+            (Severity::NonBreaking, Severity::Breaking)
+        );
+        assert_eq!(
+            severities(&old, &schema(json!({}))),
+            (Severity::Breaking, Severity::NonBreaking)
+        );
+    }
+
+    #[test]
+    fn diff_schema_items_on_one_side() {
+        let untyped = schema(json!({"type": "array"}));
+        let strings = schema(json!({"type": "array", "items": {"type": "string"}}));
+
+        let changes = |old: &Schema, new: &Schema| -> Vec<(String, Severity)> {
+            diff_schemas(old, new, Usage::Write)
+                .into_iter()
+                .map(|change| (change.path, change.severity))
+                .collect()
+        };
+        assert_eq!(
+            changes(&untyped, &strings),
+            vec![("$[*]".to_string(), Severity::Breaking)]
+        );
+        assert_eq!(
+            changes(&strings, &untyped),
+            vec![("$[*]".to_string(), Severity::NonBreaking)]
+        );
+    }
+
+    #[test]
+    fn diff_schema_additional_properties() {
+        let old = schema(json!({"additionalProperties": {"type": "string"}}));
+        let new = schema(json!({"additionalProperties": {"type": "integer"}}));
+
+        let changes = diff_schemas(&old, &new, Usage::Read);
+        assert_eq!(
+            changes,
+            vec![SchemaChange {
+                path: "$.*".to_string(),
+                kind: SchemaChangeKind::TypeChanged {
+                    old: Some(BTreeSet::from(["string".to_string()])),
+                    new: Some(BTreeSet::from(["integer".to_string()])),
+                },
+                severity: Severity::Breaking,
+            }]
+        );
+
+        let any = schema(json!({}));
+        assert_eq!(diff_schemas(&old, &any, Usage::Read)[0].path, "$.*");
+        assert!(diff_schemas(&old, &any, Usage::Write)
+            .iter()
+            .all(|change| change.severity == Severity::NonBreaking));
+
+        let closed = schema(json!({"additionalProperties": false}));
+        assert_eq!(
+            diff_schemas(&old, &closed, Usage::Write)[0].kind,
+            SchemaChangeKind::AdditionalPropertiesRejected
+        );
+        assert_eq!(diff_schemas(&old, &closed, Usage::Write).len(), 1);
+    }
+}
@@ -0,0 +1,100 @@
+//! Proptest strategies shared by the property tests
+
+use proptest::prelude::*;
+use serde_json::{Map, Value};
+
+/// Object keys, including the empty key and keys made of the `.`, `[`, `]`
+/// and `'` that delimit delta paths
+pub fn key() -> impl Strategy<Value = String> {
+    prop_oneof![4 => "[a-d]{1,2}", 1 => "[a.\\[\\]'\\\\]{0,3}", 1 => ".{0,6}"]
+}
+
+/// Scalars, including the edge cases of numbers and strings
+pub fn scalar() -> impl Strategy<Value = Value> {
+    prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::from),
+        (-5i64..5).prop_map(Value::from),
+        any::<i64>().prop_map(Value::from),
+        any::<u64>().prop_map(Value::from),
+        any::<f64>().prop_map(Value::from),
+        "[a-c]{0,2}".prop_map(Value::from),
+        any::<String>().prop_map(Value::from),
+    ]
+}
+
+/// Arbitrary nested values, small enough for the values generated for both
+/// sides of a diff to share keys and items often
+pub fn json_value() -> impl Strategy<Value = Value> {
+    scalar().prop_recursive(5, 64, 5, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..5).prop_map(Value::Array),
+            prop::collection::btree_map(key(), inner, 0..5)
+                .prop_map(|map| Value::Object(map.into_iter().collect::<Map<_, _>>())),
+        ]
+    })
+}
+
+/// A value and a modified copy of it, most of their content being shared
+pub fn json_pair() -> impl Strategy<Value = (Value, Value)> {
+    (json_value(), json_value(), any::<prop::sample::Index>()).prop_map(|(left, other, index)| {
+        let mut right = left.clone();
+        mutate(&mut right, other, index.index(usize::MAX));
+        (left, right)
+    })
+}
+
+/// A value and two modified copies of it
+pub fn json_triple() -> impl Strategy<Value = (Value, Value, Value)> {
+    (json_pair(), json_value(), any::<prop::sample::Index>()).prop_map(
+        |((base, ours), other, index)| {
+            let mut theirs = base.clone();
+            mutate(&mut theirs, other, index.index(usize::MAX));
+            (base, ours, theirs)
+        },
+    )
+}
+
+/// A value followed by two successive modifications of it
+pub fn json_chain() -> impl Strategy<Value = (Value, Value, Value)> {
+    (json_pair(), json_value(), any::<prop::sample::Index>()).prop_map(
+        |((first, second), other, index)| {
+            let mut third = second.clone();
+            mutate(&mut third, other, index.index(usize::MAX));
+            (first, second, third)
+        },
+    )
+}
+
+/// Replaces, inserts or removes a descendant of `value`, picked with `seed`
+fn mutate(value: &mut Value, other: Value, seed: usize) {
+    match value {
+        Value::Object(map) if !map.is_empty() && !seed.is_multiple_of(4) => {
+            let key = map
+                .keys()
+                .nth(seed % map.len())
+                .cloned()
+                .unwrap_or_default();
+            match seed % 7 {
+                0 => {
+                    map.remove(&key);
+                }
+                1 => {
+                    map.insert(format!("{}_", key), other);
+                }
+                _ => mutate(&mut map[&key], other, seed / 7),
+            }
+        }
+        Value::Array(array) if !array.is_empty() && !seed.is_multiple_of(4) => {
+            let index = seed % array.len();
+            match seed % 7 {
+                0 => {
+                    array.remove(index);
+                }
+                1 => array.insert(index, other),
+                _ => mutate(&mut array[index], other, seed / 7),
+            }
+        }
+        _ => *value = other,
+    }
+}
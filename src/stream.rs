@@ -0,0 +1,609 @@
+//! Streaming diff of JSON documents read from two `Read` sources.
+//!
+//! Unlike `Differ`, the documents are never loaded in memory: both sources are
+//! read token by token and compared as they go. Object keys are matched with
+//! a merge join, so both sources must list the keys of every object in the
+//! same sorted order, which is the order `serde_json` writes by default
+//! (`jq -S` sorts an existing file).
+//!
+//! A source may hold several top level documents, separated by whitespace or
+//! new lines as in NDJSON. Documents are compared pairwise, in order, and a
+//! document only found in one source is reported whole, on the `$` path.
+
+use crate::delta::{Delta, Operation};
+use crate::errors::ProcessError;
+use crate::utils::{push_index, push_key};
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use std::io::{BufRead, BufReader, Read};
+use std::ops::ControlFlow;
+
+/// Result of a step of the comparison, `Break` when the callback asked to stop
+type Step = Result<ControlFlow<()>, ProcessError>;
+
+/// Returns early when the callback asked to stop
+macro_rules! proceed {
+    ($step:expr) => {
+        if let ControlFlow::Break(()) = $step? {
+            return Ok(ControlFlow::Break(()));
+        }
+    };
+}
+
+/// Compares the documents of two `Read` sources without loading them in memory
+pub struct StreamDiffer<L: Read, R: Read> {
+    left: Parser<L>,
+    right: Parser<R>,
+}
+
+impl<L: Read, R: Read> StreamDiffer<L, R> {
+    pub fn new(left: L, right: R) -> Self {
+        StreamDiffer {
+            left: Parser::new(left),
+            right: Parser::new(right),
+        }
+    }
+
+    /// Compares the sources and calls `callback` with the index of the
+    /// document and the delta for every difference, in document order.
+    ///
+    /// Deltas have the same shape as the ones of `Differ::diff`. Returning
+    /// `ControlFlow::Break` from the callback stops reading the sources.
+    pub fn diff<F>(&mut self, mut callback: F) -> Result<(), ProcessError>
+    where
+        F: FnMut(usize, Delta) -> ControlFlow<()>,
+    {
+        let mut document = 0;
+        let mut path = String::from("$");
+
+        loop {
+            let left = self.left.next_event()?;
+            let right = self.right.next_event()?;
+
+            let mut emit = |operation: Operation, path: &str, old: Value, new: Value| {
+                callback(document, Delta::new(operation, path.to_string(), old, new))
+            };
+
+            let step = match (left, right) {
+                (None, None) => return Ok(()),
+                (Some(left), Some(right)) => compare(
+                    &mut self.left,
+                    &mut self.right,
+                    left,
+                    right,
+                    &mut path,
+                    &mut emit,
+                ),
+                (Some(left), None) => {
+                    whole(&mut self.left, Operation::Delete, left, &path, &mut emit)
+                }
+                (None, Some(right)) => {
+                    whole(&mut self.right, Operation::Add, right, &path, &mut emit)
+                }
+            };
+
+            if let ControlFlow::Break(()) = step? {
+                return Ok(());
+            }
+
+            document += 1;
+        }
+    }
+}
+
+/// Compares the values starting with the given events on both sides
+fn compare<L, R, F>(
+    left: &mut Parser<L>,
+    right: &mut Parser<R>,
+    left_event: Event,
+    right_event: Event,
+    path: &mut String,
+    emit: &mut F,
+) -> Step
+where
+    L: Read,
+    R: Read,
+    F: FnMut(Operation, &str, Value, Value) -> ControlFlow<()>,
+{
+    let length = path.len();
+
+    match (left_event, right_event) {
+        (Event::Scalar(left_value), Event::Scalar(right_value)) => {
+            if left_value != right_value {
+                proceed!(Ok(emit(Operation::Change, path, left_value, right_value)));
+            }
+        }
+        (Event::StartObject, Event::StartObject) => {
+            let mut left_key = left.next_key()?;
+            let mut right_key = right.next_key()?;
+
+            if left_key.is_none() != right_key.is_none() && length > 1 {
+                let left_value = left.read_object(left_key)?;
+                let right_value = right.read_object(right_key)?;
+                proceed!(Ok(emit(Operation::Change, path, left_value, right_value)));
+                return Ok(ControlFlow::Continue(()));
+            }
+
+            loop {
+                let order = match (&left_key, &right_key) {
+                    (None, None) => break,
+                    (Some(_), None) => Ordering::Less,
+                    (None, Some(_)) => Ordering::Greater,
+                    (Some(key), Some(other)) => key.cmp(other),
+                };
+
+                match order {
+                    Ordering::Equal => {
+                        let key = left_key.take().unwrap_or_default();
+                        push_key(path, &key);
+                        let left_event = left.expect_event()?;
+                        let right_event = right.expect_event()?;
+                        proceed!(compare(left, right, left_event, right_event, path, emit));
+                        left_key = left.next_key_after(&key)?;
+                        right_key = right.next_key_after(&key)?;
+                    }
+                    Ordering::Less => {
+                        let key = left_key.take().unwrap_or_default();
+                        push_key(path, &key);
+                        let event = left.expect_event()?;
+                        proceed!(whole(left, Operation::Delete, event, path, emit));
+                        left_key = left.next_key_after(&key)?;
+                    }
+                    Ordering::Greater => {
+                        let key = right_key.take().unwrap_or_default();
+                        push_key(path, &key);
+                        let event = right.expect_event()?;
+                        proceed!(whole(right, Operation::Add, event, path, emit));
+                        right_key = right.next_key_after(&key)?;
+                    }
+                }
+                path.truncate(length);
+            }
+        }
+        (Event::StartArray, Event::StartArray) => {
+            let mut index = 0;
+            let mut left_item = left.next_item()?;
+            let mut right_item = right.next_item()?;
+
+            if left_item.is_none() != right_item.is_none() && length > 1 {
+                let left_value = left.read_array(left_item)?;
+                let right_value = right.read_array(right_item)?;
+                proceed!(Ok(emit(Operation::Change, path, left_value, right_value)));
+                return Ok(ControlFlow::Continue(()));
+            }
+
+            loop {
+                push_index(path, index);
+                let items = match index {
+                    0 => (left_item.take(), right_item.take()),
+                    _ => (left.next_item()?, right.next_item()?),
+                };
+                match items {
+                    (None, None) => break,
+                    (Some(left_event), Some(right_event)) => {
+                        proceed!(compare(left, right, left_event, right_event, path, emit));
+                    }
+                    (Some(event), None) => {
+                        proceed!(whole(left, Operation::Delete, event, path, emit));
+                        while let Some(event) = left.next_item()? {
+                            index += 1;
+                            path.truncate(length);
+                            push_index(path, index);
+                            proceed!(whole(left, Operation::Delete, event, path, emit));
+                        }
+                        break;
+                    }
+                    (None, Some(event)) => {
+                        proceed!(whole(right, Operation::Add, event, path, emit));
+                        while let Some(event) = right.next_item()? {
+                            index += 1;
+                            path.truncate(length);
+                            push_index(path, index);
+                            proceed!(whole(right, Operation::Add, event, path, emit));
+                        }
+                        break;
+                    }
+                }
+                index += 1;
+                path.truncate(length);
+            }
+        }
+        (left_event, right_event) => {
+            let left_value = left.read_value(left_event)?;
+            let right_value = right.read_value(right_event)?;
+            proceed!(Ok(emit(Operation::Change, path, left_value, right_value)));
+        }
+    }
+
+    path.truncate(length);
+    Ok(ControlFlow::Continue(()))
+}
+
+/// Emits a single delta for a value that only exists on one side
+fn whole<S, F>(
+    parser: &mut Parser<S>,
+    operation: Operation,
+    event: Event,
+    path: &str,
+    emit: &mut F,
+) -> Step
+where
+    S: Read,
+    F: FnMut(Operation, &str, Value, Value) -> ControlFlow<()>,
+{
+    let value = parser.read_value(event)?;
+    let step = match operation {
+        Operation::Add => emit(operation, path, Value::Null, value),
+        _ => emit(operation, path, value, Value::Null),
+    };
+    proceed!(Ok(step));
+
+    Ok(ControlFlow::Continue(()))
+}
+
+#[derive(Debug, PartialEq)]
+enum Event {
+    StartObject,
+    EndObject,
+    StartArray,
+    EndArray,
+    Key(String),
+    Scalar(Value),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Expect {
+    /// First key of an object, or its end
+    KeyOrEnd,
+    /// A key after a comma
+    Key,
+    /// First value of an array, or its end
+    ValueOrEnd,
+    /// A value after a colon or a comma
+    Value,
+    /// A comma or the end of the current container
+    CommaOrEnd,
+}
+
+struct Frame {
+    is_object: bool,
+    expect: Expect,
+}
+
+/// Pull parser turning a `Read` source into a sequence of events
+struct Parser<R: Read> {
+    reader: BufReader<R>,
+    stack: Vec<Frame>,
+    offset: usize,
+}
+
+impl<R: Read> Parser<R> {
+    fn new(reader: R) -> Self {
+        Parser {
+            reader: BufReader::new(reader),
+            stack: Vec::new(),
+            offset: 0,
+        }
+    }
+
+    /// Returns the next event, or `None` at the end of the source
+    fn next_event(&mut self) -> Result<Option<Event>, ProcessError> {
+        loop {
+            self.skip_whitespace()?;
+
+            let (is_object, expect) = match self.stack.last() {
+                Some(frame) => (frame.is_object, frame.expect),
+                None => {
+                    return match self.peek()? {
+                        None => Ok(None),
+                        Some(_) => self.parse_value().map(Some),
+                    };
+                }
+            };
+
+            let byte = match self.peek()? {
+                Some(byte) => byte,
+                None => return Err(self.error("unexpected end of input")),
+            };
+
+            match (is_object, expect, byte) {
+                (true, Expect::KeyOrEnd | Expect::CommaOrEnd, b'}')
+                | (false, Expect::ValueOrEnd | Expect::CommaOrEnd, b']') => {
+                    self.consume();
+                    self.stack.pop();
+                    self.value_done();
+                    return Ok(Some(if is_object {
+                        Event::EndObject
+                    } else {
+                        Event::EndArray
+                    }));
+                }
+                (true, Expect::KeyOrEnd | Expect::Key, b'"') => {
+                    let key = self.parse_string()?;
+                    self.skip_whitespace()?;
+                    if self.peek()? != Some(b':') {
+                        return Err(self.error("expected ':'"));
+                    }
+                    self.consume();
+                    self.expect(Expect::Value);
+                    return Ok(Some(Event::Key(key)));
+                }
+                (_, Expect::CommaOrEnd, b',') => {
+                    self.consume();
+                    self.expect(if is_object {
+                        Expect::Key
+                    } else {
+                        Expect::Value
+                    });
+                }
+                (_, Expect::ValueOrEnd | Expect::Value, _) => {
+                    return self.parse_value().map(Some);
+                }
+                _ => return Err(self.error(&format!("unexpected '{}'", byte as char))),
+            }
+        }
+    }
+
+    /// Returns the next event, failing at the end of the source
+    fn expect_event(&mut self) -> Result<Event, ProcessError> {
+        match self.next_event()? {
+            Some(event) => Ok(event),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    /// Returns the next key of the current object, or `None` at its end
+    fn next_key(&mut self) -> Result<Option<String>, ProcessError> {
+        match self.expect_event()? {
+            Event::Key(key) => Ok(Some(key)),
+            Event::EndObject => Ok(None),
+            event => Err(self.unexpected(&event)),
+        }
+    }
+
+    /// Returns the next key of the current object, checking that it comes
+    /// after the previous one
+    fn next_key_after(&mut self, previous: &str) -> Result<Option<String>, ProcessError> {
+        let key = self.next_key()?;
+        if let Some(key) = &key {
+            if key.as_str() <= previous {
+                return Err(self.error(&format!(
+                    "key '{}' comes after '{}', object keys must be sorted",
+                    key, previous
+                )));
+            }
+        }
+        Ok(key)
+    }
+
+    /// Returns the first event of the next item of the current array, or
+    /// `None` at its end
+    fn next_item(&mut self) -> Result<Option<Event>, ProcessError> {
+        match self.expect_event()? {
+            Event::EndArray => Ok(None),
+            event => Ok(Some(event)),
+        }
+    }
+
+    /// Reads the whole value starting with the given event
+    fn read_value(&mut self, event: Event) -> Result<Value, ProcessError> {
+        match event {
+            Event::Scalar(value) => Ok(value),
+            Event::StartObject => {
+                let key = self.next_key()?;
+                self.read_object(key)
+            }
+            Event::StartArray => {
+                let item = self.next_item()?;
+                self.read_array(item)
+            }
+            event => Err(self.unexpected(&event)),
+        }
+    }
+
+    /// Reads the rest of an object whose first key was already read
+    fn read_object(&mut self, mut key: Option<String>) -> Result<Value, ProcessError> {
+        let mut map = Map::new();
+        while let Some(current) = key {
+            let event = self.expect_event()?;
+            map.insert(current, self.read_value(event)?);
+            key = self.next_key()?;
+        }
+        Ok(Value::Object(map))
+    }
+
+    /// Reads the rest of an array whose first item was already started
+    fn read_array(&mut self, mut item: Option<Event>) -> Result<Value, ProcessError> {
+        let mut array = Vec::new();
+        while let Some(event) = item {
+            array.push(self.read_value(event)?);
+            item = self.next_item()?;
+        }
+        Ok(Value::Array(array))
+    }
+
+    fn parse_value(&mut self) -> Result<Event, ProcessError> {
+        let byte = match self.peek()? {
+            Some(byte) => byte,
+            None => return Err(self.error("unexpected end of input")),
+        };
+
+        match byte {
+            b'{' => {
+                self.consume();
+                self.stack.push(Frame {
+                    is_object: true,
+                    expect: Expect::KeyOrEnd,
+                });
+                Ok(Event::StartObject)
+            }
+            b'[' => {
+                self.consume();
+                self.stack.push(Frame {
+                    is_object: false,
+                    expect: Expect::ValueOrEnd,
+                });
+                Ok(Event::StartArray)
+            }
+            b'"' => {
+                let value = self.parse_string()?;
+                self.value_done();
+                Ok(Event::Scalar(Value::String(value)))
+            }
+            b't' => self.parse_literal("true", Value::Bool(true)),
+            b'f' => self.parse_literal("false", Value::Bool(false)),
+            b'n' => self.parse_literal("null", Value::Null),
+            b'-' | b'0'..=b'9' => self.parse_number(),
+            byte => Err(self.error(&format!("unexpected '{}'", byte as char))),
+        }
+    }
+
+    fn parse_literal(&mut self, literal: &str, value: Value) -> Result<Event, ProcessError> {
+        for expected in literal.bytes() {
+            if self.peek()? != Some(expected) {
+                return Err(self.error(&format!("expected '{}'", literal)));
+            }
+            self.consume();
+        }
+        self.value_done();
+        Ok(Event::Scalar(value))
+    }
+
+    fn parse_number(&mut self) -> Result<Event, ProcessError> {
+        let mut number = String::new();
+        while let Some(byte) = self.peek()? {
+            if !matches!(byte, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') {
+                break;
+            }
+            number.push(byte as char);
+            self.consume();
+        }
+
+        let value: Value = serde_json::from_str(&number)
+            .map_err(|_| self.error(&format!("invalid number '{}'", number)))?;
+        self.value_done();
+        Ok(Event::Scalar(value))
+    }
+
+    fn parse_string(&mut self) -> Result<String, ProcessError> {
+        // the opening quote
+        self.consume();
+
+        let mut bytes = Vec::new();
+        loop {
+            let byte = match self.peek()? {
+                Some(byte) => byte,
+                None => return Err(self.error("unterminated string")),
+            };
+            self.consume();
+
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let escaped = match self.peek()? {
+                        Some(byte) => byte,
+                        None => return Err(self.error("unterminated string")),
+                    };
+                    self.consume();
+                    match escaped {
+                        b'"' | b'\\' | b'/' => bytes.push(escaped),
+                        b'b' => bytes.push(b'\x08'),
+                        b'f' => bytes.push(b'\x0c'),
+                        b'n' => bytes.push(b'\n'),
+                        b'r' => bytes.push(b'\r'),
+                        b't' => bytes.push(b'\t'),
+                        b'u' => {
+                            let character = self.parse_unicode_escape()?;
+                            let mut buffer = [0; 4];
+                            bytes.extend_from_slice(character.encode_utf8(&mut buffer).as_bytes());
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    }
+                }
+                byte if byte < 0x20 => return Err(self.error("control character in string")),
+                byte => bytes.push(byte),
+            }
+        }
+
+        String::from_utf8(bytes).map_err(|_| self.error("invalid UTF-8 in string"))
+    }
+
+    fn parse_unicode_escape(&mut self) -> Result<char, ProcessError> {
+        let high = self.parse_hex()?;
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high).ok_or_else(|| self.error("invalid unicode escape"));
+        }
+
+        for expected in [b'\\', b'u'] {
+            if self.peek()? != Some(expected) {
+                return Err(self.error("unpaired surrogate"));
+            }
+            self.consume();
+        }
+
+        let low = self.parse_hex()?;
+        if !(0xDC00..0xE000).contains(&low) {
+            return Err(self.error("unpaired surrogate"));
+        }
+
+        char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+            .ok_or_else(|| self.error("invalid unicode escape"))
+    }
+
+    fn parse_hex(&mut self) -> Result<u32, ProcessError> {
+        let mut value = 0;
+        for _ in 0..4 {
+            let digit = match self.peek()? {
+                Some(byte) => (byte as char).to_digit(16),
+                None => None,
+            };
+            match digit {
+                Some(digit) => value = value * 16 + digit,
+                None => return Err(self.error("invalid unicode escape")),
+            }
+            self.consume();
+        }
+        Ok(value)
+    }
+
+    /// Marks the end of a value in the current container
+    fn value_done(&mut self) {
+        self.expect(Expect::CommaOrEnd);
+    }
+
+    fn expect(&mut self, expect: Expect) {
+        if let Some(frame) = self.stack.last_mut() {
+            frame.expect = expect;
+        }
+    }
+
+    fn skip_whitespace(&mut self) -> Result<(), ProcessError> {
+        while let Some(b' ' | b'\n' | b'\r' | b'\t') = self.peek()? {
+            self.consume();
+        }
+        Ok(())
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>, ProcessError> {
+        let buffer = self.reader.fill_buf().map_err(|error| ProcessError::Io {
+            message: error.to_string(),
+        })?;
+        Ok(buffer.first().copied())
+    }
+
+    fn consume(&mut self) {
+        self.reader.consume(1);
+        self.offset += 1;
+    }
+
+    fn unexpected(&self, event: &Event) -> ProcessError {
+        self.error(&format!("unexpected {:?}", event))
+    }
+
+    fn error(&self, message: &str) -> ProcessError {
+        ProcessError::InvalidJson {
+            offset: self.offset,
+            message: message.to_string(),
+        }
+    }
+}
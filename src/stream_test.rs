@@ -0,0 +1,144 @@
+#[cfg(test)]
+mod tests {
+    use crate::delta::{Delta, Operation};
+    use crate::differ::Differ;
+    use crate::errors::ProcessError;
+    use crate::stream::StreamDiffer;
+    use serde_json::{json, Value};
+    use std::ops::ControlFlow;
+
+    fn stream_diff(left: &str, right: &str) -> Result<Vec<(usize, Delta)>, ProcessError> {
+        let mut deltas = Vec::new();
+        StreamDiffer::new(left.as_bytes(), right.as_bytes()).diff(|document, delta| {
+            deltas.push((document, delta));
+            ControlFlow::Continue(())
+        })?;
+        Ok(deltas)
+    }
+
+    fn sorted_paths(deltas: &[Delta]) -> Vec<(String, Operation, Value, Value)> {
+        let mut paths: Vec<(String, Operation, Value, Value)> = deltas
+            .iter()
+            .map(|delta| {
+                (
+                    delta.path.clone(),
+                    delta.operation.clone(),
+                    delta.old_value.clone(),
+                    delta.new_value.clone(),
+                )
+            })
+            .collect();
+        paths.sort_by(|a, b| a.0.cmp(&b.0));
+        paths
+    }
+
+    #[test]
+    fn stream_diff_matches_differ() {
+        let cases = vec![
+            (json!({"a": 1}), json!({"a": 1})),
+            (json!({"a": 1, "b": 2}), json!({"a": 2, "c": 3})),
+            (
+                json!({"a": {"b": [1, 2, 3], "c": "d"}, "e": null}),
+                json!({"a": {"b": [1, 4], "c": "d", "f": [true]}, "e": false}),
+            ),
+            (
+                json!({"a": [[1, 2], {"b": 1}]}),
+                json!({"a": [[1, 2, 3], {"b": 1, "c": {"d": "e"}}, 5]}),
+            ),
+            (
+                json!({"a": {"b": [1, 2]}, "c": 1}),
+                json!({"a": 1, "c": {"d": true}}),
+            ),
+            (
+                json!({"text": "caf\u{e9} \u{1F600}"}),
+                json!({"text": "cafe"}),
+            ),
+            (
+                json!({"a": {}, "b": [1, 2], "c": [[], {}]}),
+                json!({"a": {"d": 1}, "b": [], "e": {}}),
+            ),
+            (json!({}), json!({"a": [], "b": {"c": {}}})),
+            (json!([[1], []]), json!([])),
+        ];
+
+        for (left, right) in cases {
+            let streamed = stream_diff(&left.to_string(), &right.to_string()).unwrap();
+            assert!(streamed.iter().all(|(document, _)| *document == 0));
+
+            let streamed: Vec<Delta> = streamed.into_iter().map(|(_, delta)| delta).collect();
+            let mut differ = Differ::new_from_json_values(left.clone(), right.clone());
+            let expected = differ.diff().get_deltas();
+
+            assert_eq!(
+                sorted_paths(&streamed),
+                sorted_paths(expected),
+                "{} vs {}",
+                left,
+                right
+            );
+        }
+    }
+
+    #[test]
+    fn stream_diff_documents() {
+        let left = "{\"id\": 1, \"name\": \"a\"}\n{\"id\": 2, \"name\": \"b\"}\n{\"id\": 3}\n";
+        let right = "{\"id\": 1, \"name\": \"a\"}\n{\"id\": 2, \"name\": \"c\"}\n";
+
+        let deltas = stream_diff(left, right).unwrap();
+
+        assert_eq!(deltas.len(), 2, "Expected 2 deltas, got {}", deltas.len());
+
+        let (document, delta) = &deltas[0];
+        assert_eq!(*document, 1);
+        assert_eq!(delta.path, "$.name");
+        assert_eq!(delta.operation, Operation::Change);
+        assert_eq!(delta.old_value, json!("b"));
+        assert_eq!(delta.new_value, json!("c"));
+
+        let (document, delta) = &deltas[1];
+        assert_eq!(*document, 2);
+        assert_eq!(delta.path, "$");
+        assert_eq!(delta.operation, Operation::Delete);
+        assert_eq!(delta.old_value, json!({"id": 3}));
+    }
+
+    #[test]
+    fn stream_diff_stops_early() {
+        let left = json!({"a": 1, "b": 2, "c": 3}).to_string();
+        let right = json!({"a": 2, "b": 3, "c": 4}).to_string();
+
+        let mut count = 0;
+        StreamDiffer::new(left.as_bytes(), right.as_bytes())
+            .diff(|_, _| {
+                count += 1;
+                ControlFlow::Break(())
+            })
+            .unwrap();
+
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn stream_diff_errors() {
+        assert!(matches!(
+            stream_diff("{\"a\": 1", "{\"a\": 1}"),
+            Err(ProcessError::InvalidJson { .. })
+        ));
+        assert!(matches!(
+            stream_diff("{\"a\": tru}", "{\"a\": true}"),
+            Err(ProcessError::InvalidJson { .. })
+        ));
+        assert!(matches!(
+            stream_diff("{\"b\": 1, \"a\": 2}", "{\"a\": 2, \"b\": 1}"),
+            Err(ProcessError::InvalidJson { .. })
+        ));
+    }
+
+    #[test]
+    fn stream_diff_escapes() {
+        let left = r#"{"a": "caf\u00e9 \ud83d\ude00", "b": "tab\tquote\""}"#;
+        let right = "{\"a\": \"caf\u{e9} \u{1F600}\", \"b\": \"tab\\tquote\\\"\"}";
+
+        assert_eq!(stream_diff(left, right).unwrap().len(), 0);
+    }
+}
@@ -0,0 +1,602 @@
+//! Span-aware parsing of JSON text, and edits that rewrite only the spans of
+//! the values that change.
+//!
+//! Comments and trailing commas are accepted, as in JSONC, and kept as they
+//! are outside of the rewritten spans.
+
+use crate::delta::{Position, Span};
+use crate::errors::ProcessError;
+use crate::patcher::MAX_PATH_DEPTH;
+use crate::utils::{push_index, push_key};
+use serde::Serialize;
+use serde_json::ser::PrettyFormatter;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// A value of the text, between the byte offsets `start` and `end`
+#[derive(Debug)]
+pub(crate) struct Node {
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    pub(crate) kind: Kind,
+}
+
+#[derive(Debug)]
+pub(crate) enum Kind {
+    Scalar(Value),
+    Object(Vec<Entry>),
+    Array(Vec<Node>),
+}
+
+/// A key of an object and its value, `key_end` is past the closing quote
+#[derive(Debug)]
+pub(crate) struct Entry {
+    pub(crate) key: String,
+    pub(crate) key_start: usize,
+    pub(crate) key_end: usize,
+    pub(crate) node: Node,
+}
+
+impl Node {
+    /// Returns the value of the node
+    pub(crate) fn value(&self) -> Value {
+        match &self.kind {
+            Kind::Scalar(value) => value.clone(),
+            Kind::Object(entries) => Value::Object(
+                entries
+                    .iter()
+                    .map(|entry| (entry.key.clone(), entry.node.value()))
+                    .collect::<Map<_, _>>(),
+            ),
+            Kind::Array(elements) => Value::Array(elements.iter().map(Node::value).collect()),
+        }
+    }
+}
+
+/// Parses a JSON document, which may contain comments and trailing commas
+pub(crate) fn parse(text: &str) -> Result<Node, ProcessError> {
+    let mut parser = Parser {
+        text,
+        bytes: text.as_bytes(),
+        position: 0,
+    };
+
+    parser.skip_trivia()?;
+    let node = parser.parse_value(0)?;
+    parser.skip_trivia()?;
+    if parser.position < parser.bytes.len() {
+        return Err(parser.error("trailing characters"));
+    }
+
+    Ok(node)
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn parse_value(&mut self, depth: usize) -> Result<Node, ProcessError> {
+        if depth > MAX_PATH_DEPTH {
+            return Err(self.error("recursion limit exceeded"));
+        }
+
+        let start = self.position;
+        let kind = match self.peek() {
+            Some(b'{') => self.parse_object(depth)?,
+            Some(b'[') => self.parse_array(depth)?,
+            Some(b'"') => Kind::Scalar(Value::String(self.parse_string()?)),
+            Some(_) => {
+                let length = self.bytes[start..]
+                    .iter()
+                    .position(|byte| b",:]}/ \t\r\n".contains(byte))
+                    .unwrap_or(self.bytes.len() - start);
+                let token = &self.text[start..start + length];
+                let value = serde_json::from_str(token)
+                    .map_err(|_| self.error(&format!("unexpected {:?}", token)))?;
+                self.position += length;
+                Kind::Scalar(value)
+            }
+            None => return Err(self.error("unexpected end of text")),
+        };
+
+        Ok(Node {
+            start,
+            end: self.position,
+            kind,
+        })
+    }
+
+    fn parse_object(&mut self, depth: usize) -> Result<Kind, ProcessError> {
+        let mut entries: Vec<Entry> = Vec::new();
+        self.position += 1;
+
+        loop {
+            self.skip_trivia()?;
+            if self.peek() == Some(b'}') {
+                self.position += 1;
+                return Ok(Kind::Object(entries));
+            }
+            if !entries.is_empty() {
+                self.expect(b',')?;
+                self.skip_trivia()?;
+                if self.peek() == Some(b'}') {
+                    self.position += 1;
+                    return Ok(Kind::Object(entries));
+                }
+            }
+
+            let key_start = self.position;
+            if self.peek() != Some(b'"') {
+                return Err(self.error("expected a key"));
+            }
+            let key = self.parse_string()?;
+            let key_end = self.position;
+
+            self.skip_trivia()?;
+            self.expect(b':')?;
+            self.skip_trivia()?;
+            let node = self.parse_value(depth + 1)?;
+
+            // the last of duplicate keys wins, as with `serde_json`
+            entries.retain(|entry| entry.key != key);
+            entries.push(Entry {
+                key,
+                key_start,
+                key_end,
+                node,
+            });
+        }
+    }
+
+    fn parse_array(&mut self, depth: usize) -> Result<Kind, ProcessError> {
+        let mut elements = Vec::new();
+        self.position += 1;
+
+        loop {
+            self.skip_trivia()?;
+            if self.peek() == Some(b']') {
+                self.position += 1;
+                return Ok(Kind::Array(elements));
+            }
+            if !elements.is_empty() {
+                self.expect(b',')?;
+                self.skip_trivia()?;
+                if self.peek() == Some(b']') {
+                    self.position += 1;
+                    return Ok(Kind::Array(elements));
+                }
+            }
+
+            elements.push(self.parse_value(depth + 1)?);
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, ProcessError> {
+        let start = self.position;
+        self.position += 1;
+
+        loop {
+            match self.peek() {
+                Some(b'"') => break,
+                Some(b'\\') => self.position += 2,
+                Some(_) => self.position += 1,
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+        self.position += 1;
+
+        let token = &self.text[start..self.position];
+        serde_json::from_str(token).map_err(|error| ProcessError::InvalidJson {
+            offset: start,
+            message: error.to_string(),
+        })
+    }
+
+    /// Skips whitespace and comments
+    fn skip_trivia(&mut self) -> Result<(), ProcessError> {
+        loop {
+            match (self.peek(), self.bytes.get(self.position + 1)) {
+                (Some(b' ' | b'\t' | b'\r' | b'\n'), _) => self.position += 1,
+                (Some(b'/'), Some(b'/')) => {
+                    self.position = self.bytes[self.position..]
+                        .iter()
+                        .position(|byte| *byte == b'\n')
+                        .map_or(self.bytes.len(), |end| self.position + end);
+                }
+                (Some(b'/'), Some(b'*')) => {
+                    let end = self.text[self.position + 2..]
+                        .find("*/")
+                        .ok_or_else(|| self.error("unterminated comment"))?;
+                    self.position += end + 4;
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), ProcessError> {
+        if self.peek() != Some(byte) {
+            return Err(self.error(&format!("expected {:?}", byte as char)));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.position).copied()
+    }
+
+    fn error(&self, message: &str) -> ProcessError {
+        ProcessError::InvalidJson {
+            offset: self.position,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Returns the span of every value of the text, by path
+pub(crate) fn spans(text: &str, node: &Node) -> HashMap<String, Span> {
+    let lines: Vec<usize> = std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(newline, _)| newline + 1))
+        .collect();
+    let mut spans = HashMap::new();
+    collect_spans(text, &lines, node, &mut "$".to_string(), &mut spans);
+    spans
+}
+
+fn collect_spans(
+    text: &str,
+    lines: &[usize],
+    node: &Node,
+    path: &mut String,
+    spans: &mut HashMap<String, Span>,
+) {
+    let span = Span {
+        start: position(text, lines, node.start),
+        end: position(text, lines, node.end),
+    };
+    spans.insert(path.clone(), span);
+
+    let length = path.len();
+    match &node.kind {
+        Kind::Scalar(_) => {}
+        Kind::Object(entries) => {
+            for entry in entries {
+                push_key(path, &entry.key);
+                collect_spans(text, lines, &entry.node, path, spans);
+                path.truncate(length);
+            }
+        }
+        Kind::Array(elements) => {
+            for (index, element) in elements.iter().enumerate() {
+                push_index(path, index);
+                collect_spans(text, lines, element, path, spans);
+                path.truncate(length);
+            }
+        }
+    }
+}
+
+/// Returns the line and column of a byte offset, given the offsets where
+/// lines start
+fn position(text: &str, lines: &[usize], offset: usize) -> Position {
+    let line = lines.partition_point(|start| *start <= offset);
+    let start = lines[line - 1];
+    Position {
+        line,
+        column: text[start..offset].chars().count() + 1,
+    }
+}
+
+/// A replacement of the bytes between `start` and `end`
+struct Edit {
+    start: usize,
+    end: usize,
+    text: String,
+}
+
+/// Rewrites `text`, parsed into `node`, to hold `value`. Only the spans of
+/// the values that differ are rewritten, new entries follow the indentation
+/// and separators of their container.
+pub(crate) fn rewrite(text: &str, node: &Node, value: &Value) -> String {
+    let mut edits = Vec::new();
+    let indent = indent_unit(text);
+    update(text, node, value, indent.as_deref(), &mut edits);
+
+    // edits at the same offset are applied longest first, so that a removal
+    // does not swallow an insertion
+    edits.sort_by_key(|edit| (edit.start, edit.end));
+    let mut result = text.to_string();
+    for edit in edits.iter().rev() {
+        result.replace_range(edit.start..edit.end, &edit.text);
+    }
+
+    result
+}
+
+fn update(text: &str, node: &Node, value: &Value, indent: Option<&str>, edits: &mut Vec<Edit>) {
+    if node.value() == *value {
+        return;
+    }
+
+    match (&node.kind, value) {
+        (Kind::Object(entries), Value::Object(object))
+            if entries.iter().any(|entry| object.contains_key(&entry.key)) =>
+        {
+            let kept: Vec<bool> = entries
+                .iter()
+                .map(|entry| object.contains_key(&entry.key))
+                .collect();
+            let spans: Vec<(usize, usize)> = entries
+                .iter()
+                .map(|entry| (entry.key_start, entry.node.end))
+                .collect();
+
+            for entry in entries {
+                if let Some(value) = object.get(&entry.key) {
+                    update(text, &entry.node, value, indent, edits);
+                }
+            }
+
+            let first = &entries[0];
+            let separator = match &text[first.key_end..first.node.start] {
+                separator if separator.trim() == ":" => separator,
+                _ => ": ",
+            };
+            let (prefix, base) = entry_prefix(text, node.start, &spans, indent);
+            let added: Vec<String> = object
+                .iter()
+                .filter(|(key, _)| !entries.iter().any(|entry| entry.key == **key))
+                .map(|(key, value)| {
+                    format!(
+                        "{}{}{}",
+                        Value::from(key.as_str()),
+                        separator,
+                        render(value, &base, indent)
+                    )
+                })
+                .collect();
+            remove_runs(text, &spans, &kept, !added.is_empty(), edits);
+            insert_after_last(text, &spans, &kept, &added, &prefix, edits);
+        }
+        (Kind::Array(elements), Value::Array(array))
+            if !elements.is_empty() && !array.is_empty() =>
+        {
+            let kept: Vec<bool> = (0..elements.len())
+                .map(|index| index < array.len())
+                .collect();
+            let spans: Vec<(usize, usize)> = elements
+                .iter()
+                .map(|element| (element.start, element.end))
+                .collect();
+
+            for (element, value) in elements.iter().zip(array) {
+                update(text, element, value, indent, edits);
+            }
+
+            let (prefix, base) = entry_prefix(text, node.start, &spans, indent);
+            let added: Vec<String> = array
+                .iter()
+                .skip(elements.len())
+                .map(|value| render(value, &base, indent))
+                .collect();
+            remove_runs(text, &spans, &kept, !added.is_empty(), edits);
+            insert_after_last(text, &spans, &kept, &added, &prefix, edits);
+        }
+        _ => edits.push(Edit {
+            start: node.start,
+            end: node.end,
+            text: render(value, line_indent(text, node.start), indent),
+        }),
+    }
+}
+
+/// Removes the runs of entries that are not kept, along with the commas and
+/// whitespace that separate them from the entries kept. At least one entry
+/// is kept.
+///
+/// A comment on the line of the last entry kept stays with it, and the comma
+/// before that comment stays only when entries are `appending`.
+fn remove_runs(
+    text: &str,
+    spans: &[(usize, usize)],
+    kept: &[bool],
+    appending: bool,
+    edits: &mut Vec<Edit>,
+) {
+    let mut index = 0;
+    while index < spans.len() {
+        if kept[index] {
+            index += 1;
+            continue;
+        }
+
+        let first = index;
+        while index < spans.len() && !kept[index] {
+            index += 1;
+        }
+
+        let (start, end) = match spans.get(index) {
+            // up to the next entry kept
+            Some((next, _)) => (spans[first].0, *next),
+            // from the end of the last entry kept, or of its comment, to the
+            // end of the last entry removed and of its comment
+            None => {
+                let last = spans[index - 1].1;
+                let end = trailing_comment(text, last).map_or(last, |(_, end)| end);
+                match trailing_comment(text, spans[first - 1].1) {
+                    Some((comma, start)) => {
+                        if let (Some(comma), false) = (comma, appending) {
+                            edits.push(Edit {
+                                start: comma,
+                                end: comma + 1,
+                                text: String::new(),
+                            });
+                        }
+                        (start, end)
+                    }
+                    None => (spans[first - 1].1, end),
+                }
+            }
+        };
+        edits.push(Edit {
+            start,
+            end,
+            text: String::new(),
+        });
+    }
+}
+
+/// Inserts new entries after the last entry kept, or after the comment on
+/// its line when they go on lines of their own
+fn insert_after_last(
+    text: &str,
+    spans: &[(usize, usize)],
+    kept: &[bool],
+    added: &[String],
+    prefix: &str,
+    edits: &mut Vec<Edit>,
+) {
+    if added.is_empty() {
+        return;
+    }
+
+    let last = (0..spans.len())
+        .rev()
+        .find(|index| kept[*index])
+        .unwrap_or(0);
+    let end = spans[last].1;
+
+    let comment = trailing_comment(text, end).filter(|_| prefix.starts_with('\n'));
+    let Some((comma, comment_end)) = comment else {
+        let text: String = added
+            .iter()
+            .map(|entry| format!(",{}{}", prefix, entry))
+            .collect();
+        edits.push(Edit {
+            start: end,
+            end,
+            text,
+        });
+        return;
+    };
+
+    if comma.is_none() {
+        edits.push(Edit {
+            start: end,
+            end,
+            text: ",".to_string(),
+        });
+    }
+    let entries: Vec<String> = added
+        .iter()
+        .map(|entry| format!("{}{}", prefix, entry))
+        .collect();
+    edits.push(Edit {
+        start: comment_end,
+        end: comment_end,
+        text: entries.join(","),
+    });
+}
+
+/// Returns the offset of the comma and the end of the comment that follow a
+/// value ending at `offset`, when the comment ends its line
+fn trailing_comment(text: &str, offset: usize) -> Option<(Option<usize>, usize)> {
+    let is_blank = |character: char| character == ' ' || character == '\t';
+    let rest = &text[offset..];
+    let after_value = rest.trim_start_matches(is_blank);
+
+    let (comma, after_comma) = match after_value.strip_prefix(',') {
+        Some(after) => (Some(text.len() - after_value.len()), after),
+        None => (None, after_value),
+    };
+    let comment = after_comma.trim_start_matches(is_blank);
+    let start = text.len() - comment.len();
+
+    let length = if comment.starts_with("//") {
+        comment.find(['\r', '\n']).unwrap_or(comment.len())
+    } else if comment.starts_with("/*") {
+        let length = comment.find("*/")? + 2;
+        let line_end = comment[length..].trim_start_matches(is_blank);
+        if comment[..length].contains('\n')
+            || !(line_end.is_empty() || line_end.starts_with(['\r', '\n']))
+        {
+            return None;
+        }
+        length
+    } else {
+        return None;
+    };
+
+    Some((comma, start + length))
+}
+
+/// Returns the whitespace to put before a new entry of a container, and the
+/// indentation of its lines
+fn entry_prefix(
+    text: &str,
+    container: usize,
+    spans: &[(usize, usize)],
+    indent: Option<&str>,
+) -> (String, String) {
+    let between = match spans {
+        [first, second, ..] => &text[first.1..second.0],
+        [first] => &text[container + 1..first.0],
+        [] => "",
+    };
+
+    match between.rfind('\n') {
+        Some(newline) => {
+            let indent = &between[newline + 1..];
+            (format!("\n{}", indent), indent.to_string())
+        }
+        None if spans.len() > 1 => {
+            let whitespace = between.len() - between.trim_end().len();
+            (
+                between[between.len() - whitespace..].to_string(),
+                String::new(),
+            )
+        }
+        // a single entry on one line, spaced as the rest of the text
+        None if indent.is_some() => (" ".to_string(), String::new()),
+        None => (String::new(), String::new()),
+    }
+}
+
+/// Returns the leading whitespace of the line holding the given offset
+fn line_indent(text: &str, offset: usize) -> &str {
+    let start = text[..offset].rfind('\n').map_or(0, |newline| newline + 1);
+    let line = &text[start..];
+    let length = line.len() - line.trim_start_matches([' ', '\t']).len();
+    &line[..length.min(offset - start)]
+}
+
+/// Returns the indentation of the first indented line, `None` for text
+/// written on one line
+fn indent_unit(text: &str) -> Option<String> {
+    text.lines()
+        .skip(1)
+        .map(|line| &line[..line.len() - line.trim_start_matches([' ', '\t']).len()])
+        .find(|indent| !indent.is_empty())
+        .map(str::to_string)
+}
+
+/// Writes a value starting on a line indented with `base`, pretty printed
+/// with `indent` when the text is
+fn render(value: &Value, base: &str, indent: Option<&str>) -> String {
+    let Some(indent) = indent else {
+        return value.to_string();
+    };
+
+    let mut bytes = Vec::new();
+    let formatter = PrettyFormatter::with_indent(indent.as_bytes());
+    let mut serializer = serde_json::Serializer::with_formatter(&mut bytes, formatter);
+    if value.serialize(&mut serializer).is_err() {
+        return value.to_string();
+    }
+
+    String::from_utf8_lossy(&bytes).replace('\n', &format!("\n{}", base))
+}
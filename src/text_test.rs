@@ -0,0 +1,52 @@
+#[cfg(test)]
+mod tests {
+    use crate::errors::ProcessError;
+    use crate::text::{parse, Kind};
+    use serde_json::json;
+
+    #[test]
+    fn parse_spans() {
+        let text = r#"{"a": [1, 2.50], "b": "x"}"#;
+        let node = parse(text).unwrap();
+
+        assert_eq!((node.start, node.end), (0, text.len()));
+        let Kind::Object(entries) = &node.kind else {
+            panic!("not an object");
+        };
+        assert_eq!(&text[entries[0].key_start..entries[0].key_end], r#""a""#);
+        assert_eq!(
+            &text[entries[0].node.start..entries[0].node.end],
+            "[1, 2.50]"
+        );
+        assert_eq!(&text[entries[1].node.start..entries[1].node.end], r#""x""#);
+        assert_eq!(node.value(), json!({"a": [1, 2.5], "b": "x"}));
+    }
+
+    #[test]
+    fn parse_comments_and_trailing_commas() {
+        let text = "// settings\n{\n  \"a\": 1, /* one */\n  \"b\": [true, null,],\n}\n";
+
+        assert_eq!(
+            parse(text).unwrap().value(),
+            json!({"a": 1, "b": [true, null]})
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        for (text, offset) in [
+            ("{\"a\" 1}", 5),
+            ("[1, 2", 5),
+            ("{\"a\": tru}", 6),
+            ("[1] 2", 4),
+            ("/* open", 0),
+        ] {
+            match parse(text) {
+                Err(ProcessError::InvalidJson { offset: at, .. }) => {
+                    assert_eq!(at, offset, "{}", text)
+                }
+                other => panic!("{}: {:?}", text, other),
+            }
+        }
+    }
+}
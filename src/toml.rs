@@ -0,0 +1,234 @@
+//! TOML documents, behind the `toml` feature.
+//!
+//! Documents are converted to `Value`, so deltas use the same paths as for
+//! JSON. TOML datetimes have no JSON equivalent and become strings in RFC 3339
+//! format, NaN and infinite floats become nulls, and TOML has no null, so a
+//! delta setting a null cannot be applied.
+//!
+//! `patch` edits the original document in place: unchanged keys, tables and
+//! values keep their formatting and comments, and a changed value keeps the
+//! whitespace and comments around it. A string written where a datetime was
+//! stays a datetime if it still reads as one.
+
+use crate::delta::Delta;
+use crate::errors::ProcessError;
+use crate::patcher::{try_patch, PatchOptions};
+use serde_json::{Map, Value};
+use toml_edit::{
+    Array, ArrayOfTables, Datetime, DocumentMut, InlineTable, Item, Table, TableLike, Value as Toml,
+};
+
+/// Parses a TOML document into a `Value`
+pub fn from_str(text: &str) -> Result<Value, ProcessError> {
+    Ok(table_to_json(parse(text)?.as_table()))
+}
+
+/// Writes an object as a TOML document
+pub fn to_string(value: &Value) -> Result<String, ProcessError> {
+    patch_document(DocumentMut::new(), value)
+}
+
+/// Applies deltas to a TOML document and writes it back, keeping the
+/// formatting of everything the deltas do not change
+pub fn patch(text: &str, deltas: &[Delta]) -> Result<String, ProcessError> {
+    let document = parse(text)?;
+    let value = table_to_json(document.as_table());
+    let patched = try_patch(value, deltas, PatchOptions::default())?;
+    patch_document(document, &patched)
+}
+
+fn parse(text: &str) -> Result<DocumentMut, ProcessError> {
+    text.parse()
+        .map_err(|error: toml_edit::TomlError| invalid(error.to_string()))
+}
+
+fn patch_document(mut document: DocumentMut, value: &Value) -> Result<String, ProcessError> {
+    let Value::Object(object) = value else {
+        return Err(invalid(format!("{} is not a table", value)));
+    };
+
+    update_table(document.as_table_mut(), object, false)?;
+    Ok(document.to_string())
+}
+
+fn table_to_json(table: &dyn TableLike) -> Value {
+    Value::Object(
+        table
+            .iter()
+            .map(|(key, item)| (key.to_string(), item_to_json(item)))
+            .collect(),
+    )
+}
+
+fn item_to_json(item: &Item) -> Value {
+    match item {
+        Item::None => Value::Null,
+        Item::Value(value) => to_json(value),
+        Item::Table(table) => table_to_json(table),
+        Item::ArrayOfTables(array) => {
+            Value::Array(array.iter().map(|table| table_to_json(table)).collect())
+        }
+    }
+}
+
+fn to_json(value: &Toml) -> Value {
+    match value {
+        Toml::String(value) => Value::from(value.value().as_str()),
+        Toml::Integer(value) => Value::from(*value.value()),
+        Toml::Float(value) => Value::from(*value.value()),
+        Toml::Boolean(value) => Value::from(*value.value()),
+        Toml::Datetime(value) => Value::from(value.value().to_string()),
+        Toml::Array(array) => Value::Array(array.iter().map(to_json).collect()),
+        Toml::InlineTable(table) => table_to_json(table),
+    }
+}
+
+/// Updates a table to hold `object`, touching only the keys that changed.
+/// New objects become inline tables in an `inline` table, standard tables
+/// otherwise.
+fn update_table(
+    table: &mut dyn TableLike,
+    object: &Map<String, Value>,
+    inline: bool,
+) -> Result<(), ProcessError> {
+    let removed: Vec<String> = table
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .filter(|key| !object.contains_key(key))
+        .collect();
+    for key in removed {
+        table.remove(&key);
+    }
+
+    for (key, value) in object {
+        match table.get_mut(key) {
+            Some(item) => update_item(item, value)?,
+            None => {
+                table.insert(key, to_item(value, inline)?);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn update_item(item: &mut Item, new: &Value) -> Result<(), ProcessError> {
+    match (item, new) {
+        (Item::Table(table), Value::Object(object)) => update_table(table, object, false),
+        (Item::ArrayOfTables(array), Value::Array(elements)) if is_tables(elements) => {
+            for (index, element) in elements.iter().enumerate() {
+                let Value::Object(object) = element else {
+                    continue;
+                };
+                match array.get_mut(index) {
+                    Some(table) => update_table(table, object, false)?,
+                    None => array.push(to_table(object)?),
+                }
+            }
+            while array.len() > elements.len() {
+                array.remove(array.len() - 1);
+            }
+            Ok(())
+        }
+        (Item::Value(value), new) => update_value(value, new),
+        (item, new) => {
+            *item = to_item(new, false)?;
+            Ok(())
+        }
+    }
+}
+
+fn update_value(value: &mut Toml, new: &Value) -> Result<(), ProcessError> {
+    if to_json(value) == *new {
+        return Ok(());
+    }
+
+    match (&mut *value, new) {
+        (Toml::Array(array), Value::Array(elements)) => {
+            for (index, element) in elements.iter().enumerate() {
+                match array.get_mut(index) {
+                    Some(value) => update_value(value, element)?,
+                    None => array.push(to_value(element)?),
+                }
+            }
+            while array.len() > elements.len() {
+                array.remove(array.len() - 1);
+            }
+        }
+        (Toml::InlineTable(table), Value::Object(object)) => update_table(table, object, true)?,
+        (value, new) => {
+            let datetime = match (&*value, new) {
+                (Toml::Datetime(_), Value::String(text)) => text.parse::<Datetime>().ok(),
+                _ => None,
+            };
+            let decor = value.decor().clone();
+            *value = match datetime {
+                Some(datetime) => Toml::from(datetime),
+                None => to_value(new)?,
+            };
+            *value.decor_mut() = decor;
+        }
+    }
+
+    Ok(())
+}
+
+fn to_item(value: &Value, inline: bool) -> Result<Item, ProcessError> {
+    match value {
+        Value::Object(object) if !inline => Ok(Item::Table(to_table(object)?)),
+        Value::Array(elements) if !inline && is_tables(elements) => {
+            let mut array = ArrayOfTables::new();
+            for element in elements {
+                if let Value::Object(object) = element {
+                    array.push(to_table(object)?);
+                }
+            }
+            Ok(Item::ArrayOfTables(array))
+        }
+        value => Ok(Item::Value(to_value(value)?)),
+    }
+}
+
+fn to_table(object: &Map<String, Value>) -> Result<Table, ProcessError> {
+    let mut table = Table::new();
+    update_table(&mut table, object, false)?;
+    Ok(table)
+}
+
+fn to_value(value: &Value) -> Result<Toml, ProcessError> {
+    let value = match value {
+        Value::Null => return Err(invalid("null has no TOML equivalent".to_string())),
+        Value::Bool(value) => Toml::from(*value),
+        Value::Number(number) => match number.as_i64() {
+            Some(integer) => Toml::from(integer),
+            None if number.is_f64() => Toml::from(number.as_f64().unwrap_or_default()),
+            None => return Err(invalid(format!("{} does not fit a TOML integer", number))),
+        },
+        Value::String(value) => Toml::from(value.as_str()),
+        Value::Array(elements) => Toml::Array(
+            elements
+                .iter()
+                .map(to_value)
+                .collect::<Result<Array, _>>()?,
+        ),
+        Value::Object(object) => {
+            let mut table = InlineTable::new();
+            update_table(&mut table, object, true)?;
+            Toml::InlineTable(table)
+        }
+    };
+
+    Ok(value)
+}
+
+/// Returns true for a non-empty array of objects, written as `[[name]]`
+fn is_tables(elements: &[Value]) -> bool {
+    !elements.is_empty() && elements.iter().all(Value::is_object)
+}
+
+fn invalid(message: String) -> ProcessError {
+    ProcessError::InvalidDocument {
+        format: "TOML".to_string(),
+        message,
+    }
+}
@@ -0,0 +1,79 @@
+#[cfg(test)]
+mod tests {
+    use crate::differ::Differ;
+    use crate::toml;
+    use serde_json::json;
+
+    const MANIFEST: &str = r#"# package metadata
+[package]
+name = "demo"   # the crate name
+version = "0.1.0"
+released = 1979-05-27T07:32:00Z
+
+[dependencies]
+serde = { version = "1.0", features = ["derive"] }
+
+[[bin]]
+name = "demo"
+"#;
+
+    #[test]
+    fn toml_from_str() {
+        assert_eq!(
+            toml::from_str(MANIFEST).unwrap(),
+            json!({
+                "package": {"name": "demo", "version": "0.1.0", "released": "1979-05-27T07:32:00Z"},
+                "dependencies": {"serde": {"version": "1.0", "features": ["derive"]}},
+                "bin": [{"name": "demo"}]
+            })
+        );
+        assert!(toml::from_str("a = ").is_err());
+    }
+
+    #[test]
+    fn toml_patch_keeps_formatting() {
+        let changed = MANIFEST
+            .replace("0.1.0", "0.2.0")
+            .replace("1979-05-27T07:32:00Z", "2024-01-01T00:00:00Z")
+            .replace(r#"["derive"]"#, r#"["derive", "rc"]"#);
+
+        let mut differ = Differ::new_from_toml(MANIFEST, &changed).unwrap();
+        let deltas = differ.diff().get_deltas().clone();
+        assert_eq!(deltas.len(), 3);
+
+        assert_eq!(toml::patch(MANIFEST, &deltas).unwrap(), changed);
+    }
+
+    #[test]
+    fn toml_patch_adds_and_removes() {
+        let left = toml::from_str(MANIFEST).unwrap();
+        let mut right = left.clone();
+        right["package"]["edition"] = json!("2021");
+        right["dependencies"]["rayon"] = json!({"version": "1.8", "optional": true});
+        right["features"] = json!({"parallel": ["dep:rayon"]});
+        right.as_object_mut().unwrap().remove("bin");
+
+        let mut differ = Differ::new_from_json_values(left, right.clone());
+        let patched = toml::patch(MANIFEST, differ.diff().get_deltas()).unwrap();
+
+        assert_eq!(toml::from_str(&patched).unwrap(), right);
+        assert!(patched
+            .starts_with("# package metadata\n[package]\nname = \"demo\"   # the crate name\n"));
+
+        let mut differ = Differ::new_from_json_values(json!({"a": 1}), json!({"a": null}));
+        assert!(toml::patch("a = 1\n", differ.diff().get_deltas()).is_err());
+    }
+
+    #[test]
+    fn toml_patch_dotted_table_names() {
+        let text = "[\"a.b\"]\nc = 1\n\n[a]\nb = 2\n";
+        let mut right = toml::from_str(text).unwrap();
+        right["a.b"]["c"] = json!(10);
+
+        let mut differ = Differ::new_from_json_values(toml::from_str(text).unwrap(), right);
+        assert_eq!(
+            toml::patch(text, differ.diff().get_deltas()).unwrap(),
+            "[\"a.b\"]\nc = 10\n\n[a]\nb = 2\n"
+        );
+    }
+}
@@ -0,0 +1,303 @@
+//! Rebase of concurrent delta lists, in the style of operational transforms.
+
+use crate::compose::{clashes, compose};
+use crate::delta::{compare_paths, invert, Delta, Operation};
+use crate::utils::{is_path_prefix, path_prefixes, push_index, push_key};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+
+/// Which of the two lists a delta comes from
+#[derive(Clone, Copy, PartialEq)]
+enum Side {
+    A,
+    B,
+}
+
+/// How a list changes the length of an array
+#[derive(Default)]
+struct Resize {
+    /// Number of elements added at the end
+    added: usize,
+    /// First removed index and the index past the last one
+    removed: Option<(usize, usize)>,
+}
+
+impl Resize {
+    fn removes(&self, index: usize) -> bool {
+        matches!(self.removed, Some((start, end)) if (start..end).contains(&index))
+    }
+
+    fn removed_count(&self) -> usize {
+        self.removed.map_or(0, |(start, end)| end - start)
+    }
+}
+
+/// A region both lists change, where the deltas of one list were dropped
+#[derive(Debug, PartialEq, Clone)]
+pub struct Conflict {
+    /// The shortest path of the region
+    pub path: String,
+    /// The deltas of `a` in the region
+    pub a: Vec<Delta>,
+    /// The deltas of `b` in the region
+    pub b: Vec<Delta>,
+}
+
+/// Outcome of `transform`
+#[derive(Debug, PartialEq, Clone)]
+pub struct Transformed {
+    /// `a`, rewritten to apply after `b`
+    pub a: Vec<Delta>,
+    /// `b`, rewritten to apply after `a`
+    pub b: Vec<Delta>,
+    /// The regions where edits were dropped, sorted by path
+    pub conflicts: Vec<Conflict>,
+}
+
+impl Transformed {
+    /// Returns true if no edit of either list was dropped
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+/// Rewrites two delta lists made against the same document so that each one
+/// applies after the other: `patch(patch(base, a), b')` and
+/// `patch(patch(base, b), a')` give the same document.
+///
+/// `Differ` only inserts or removes array elements at the end of an array,
+/// one whole element per delta, so the indices of the other list are shifted
+/// accordingly. Elements appended by both lists are all kept, those of `a`
+/// first, and an element removed by both is removed once. An element removed
+/// by one list drops the edits the other list makes inside it.
+///
+/// Other deltas on the same path, or on nested paths, conflict and `a` wins:
+/// the deltas of `b` are dropped and `a'` turns the value left by `b` into the
+/// value `a` produced. Every region where edits are dropped is reported in
+/// `Transformed::conflicts`.
+pub fn transform(a: &[Delta], b: &[Delta]) -> Transformed {
+    let (a, b) = (expand(a), expand(b));
+    let (a_resizes, b_resizes) = (resizes(&a), resizes(&b));
+    let mut conflicts = Vec::new();
+
+    // edits inside elements removed by the other list are dropped
+    let (a, a_dropped) = drop_removed(a, &b_resizes);
+    let (b, b_dropped) = drop_removed(b, &a_resizes);
+    for (element, dropped) in a_dropped {
+        conflicts.push(Conflict {
+            a: dropped,
+            b: deletions(&b, &element),
+            path: element,
+        });
+    }
+    for (element, dropped) in b_dropped {
+        conflicts.push(Conflict {
+            a: deletions(&a, &element),
+            b: dropped,
+            path: element,
+        });
+    }
+
+    let clashes = clashes(&a, &b);
+    let mut deltas: Vec<(&str, Side, &Delta)> = a
+        .iter()
+        .map(|delta| (Side::A, delta))
+        .chain(b.iter().map(|delta| (Side::B, delta)))
+        .map(|(side, delta)| {
+            let key = path_prefixes(&delta.path)
+                .find(|prefix| clashes.contains(prefix))
+                .unwrap_or(&delta.path);
+            (key, side, delta)
+        })
+        .collect();
+    deltas.sort_by(|(a, _, _), (b, _, _)| compare_paths(a, b));
+
+    let mut a_transformed = Vec::new();
+    let mut b_transformed = Vec::new();
+    let mut start = 0;
+    for end in 1..=deltas.len() {
+        let (root, _, _) = deltas[start];
+        if end < deltas.len() && is_path_prefix(root, deltas[end].0) {
+            continue;
+        }
+
+        let group = &deltas[start..end];
+        start = end;
+
+        let side = |wanted: Side| -> Vec<Delta> {
+            group
+                .iter()
+                .filter(|(_, side, _)| *side == wanted)
+                .map(|(_, _, delta)| (*delta).clone())
+                .collect()
+        };
+        let (ours, theirs) = (side(Side::A), side(Side::B));
+
+        if is_conflict(&ours, &theirs) {
+            // undo the changes of `b` in the region, then redo those of `a`
+            a_transformed.extend(compose(&invert(&theirs), &ours));
+            conflicts.push(Conflict {
+                path: root.to_string(),
+                a: ours,
+                b: theirs,
+            });
+            continue;
+        }
+
+        a_transformed.extend(ours.iter().map(|delta| shift(delta, &b_resizes, 0)));
+        b_transformed.extend(theirs.iter().map(|delta| {
+            let added = split_index(&delta.path)
+                .and_then(|(array, _)| a_resizes.get(array))
+                .map_or(0, |resize| resize.added);
+            shift(delta, &a_resizes, added)
+        }));
+    }
+
+    a_transformed.sort_by(|a, b| compare_paths(&a.path, &b.path));
+    b_transformed.sort_by(|a, b| compare_paths(&a.path, &b.path));
+    conflicts.sort_by(|a, b| compare_paths(&a.path, &b.path));
+
+    Transformed {
+        a: a_transformed,
+        b: b_transformed,
+        conflicts,
+    }
+}
+
+/// Rewrites the change of an empty object or array into a non-empty one as
+/// the addition of each of its entries, so that entries added concurrently
+/// to the same empty container are all kept.
+fn expand(deltas: &[Delta]) -> Vec<Delta> {
+    let mut expanded = Vec::with_capacity(deltas.len());
+
+    for delta in deltas {
+        match (&delta.operation, &delta.old_value, &delta.new_value) {
+            (Operation::Change, Value::Array(old), Value::Array(new))
+                if old.is_empty() && !new.is_empty() =>
+            {
+                for (index, value) in new.iter().enumerate() {
+                    let mut path = delta.path.clone();
+                    push_index(&mut path, index);
+                    expanded.push(Delta::new(Operation::Add, path, Value::Null, value.clone()));
+                }
+            }
+            (Operation::Change, Value::Object(old), Value::Object(new))
+                if old.is_empty() && !new.is_empty() =>
+            {
+                for (key, value) in new.iter() {
+                    let mut path = delta.path.clone();
+                    push_key(&mut path, key);
+                    expanded.push(Delta::new(Operation::Add, path, Value::Null, value.clone()));
+                }
+            }
+            _ => expanded.push(delta.clone()),
+        }
+    }
+
+    expanded
+}
+
+/// Returns the array elements inserted and removed by a list, by array path
+fn resizes(deltas: &[Delta]) -> HashMap<String, Resize> {
+    let mut resizes: HashMap<String, Resize> = HashMap::new();
+
+    for delta in deltas {
+        let Some((array, index)) = split_index(&delta.path) else {
+            continue;
+        };
+        match delta.operation {
+            Operation::Add => resizes.entry(array.to_string()).or_default().added += 1,
+            Operation::Delete => {
+                let resize = resizes.entry(array.to_string()).or_default();
+                let (start, end) = resize.removed.get_or_insert((index, index + 1));
+                *start = (*start).min(index);
+                *end = (*end).max(index + 1);
+            }
+            Operation::Change => {}
+        }
+    }
+
+    resizes
+}
+
+/// Returns the element removed by the other list that the path is, or is
+/// nested in
+fn removed_element<'p>(path: &'p str, resizes: &HashMap<String, Resize>) -> Option<&'p str> {
+    path_prefixes(path).find(|prefix| match split_index(prefix) {
+        Some((array, index)) => resizes
+            .get(array)
+            .is_some_and(|resize| resize.removes(index)),
+        None => false,
+    })
+}
+
+/// Splits off the deltas in elements removed by the other list. Returns the
+/// deltas kept, and the edits dropped by removed element: deletions are not
+/// lost, the element being removed anyway.
+fn drop_removed(
+    deltas: Vec<Delta>,
+    resizes: &HashMap<String, Resize>,
+) -> (Vec<Delta>, BTreeMap<String, Vec<Delta>>) {
+    let mut kept = Vec::with_capacity(deltas.len());
+    let mut dropped: BTreeMap<String, Vec<Delta>> = BTreeMap::new();
+
+    for delta in deltas {
+        match removed_element(&delta.path, resizes) {
+            None => kept.push(delta),
+            Some(_) if delta.operation == Operation::Delete => {}
+            Some(element) => dropped.entry(element.to_string()).or_default().push(delta),
+        }
+    }
+
+    (kept, dropped)
+}
+
+/// Returns the deletions of a list at `path`
+fn deletions(deltas: &[Delta], path: &str) -> Vec<Delta> {
+    deltas
+        .iter()
+        .filter(|delta| delta.operation == Operation::Delete && delta.path == path)
+        .cloned()
+        .collect()
+}
+
+/// Returns true if both lists change the same region of the document, other
+/// than appending elements to the same array
+fn is_conflict(ours: &[Delta], theirs: &[Delta]) -> bool {
+    if ours.is_empty() || theirs.is_empty() {
+        return false;
+    }
+
+    let appends = |deltas: &[Delta], path: &str| {
+        deltas
+            .iter()
+            .all(|delta| delta.operation == Operation::Add && delta.path == path)
+    };
+    let path = &ours[0].path;
+
+    split_index(path).is_none() || !appends(ours, path) || !appends(theirs, path)
+}
+
+/// Moves an element inserted by a list past the elements the other list
+/// removed from, or `added` to, the same array
+fn shift(delta: &Delta, resizes: &HashMap<String, Resize>, added: usize) -> Delta {
+    let (Operation::Add, Some((array, index))) = (&delta.operation, split_index(&delta.path))
+    else {
+        return delta.clone();
+    };
+
+    let removed = resizes.get(array).map_or(0, Resize::removed_count);
+    let mut path = array.to_string();
+    push_index(&mut path, index.saturating_sub(removed) + added);
+
+    Delta::new(Operation::Add, path, Value::Null, delta.new_value.clone())
+}
+
+/// Splits a path ending with an array index, such as `$.a[2]`, into the path
+/// of the array and the index
+pub(crate) fn split_index(path: &str) -> Option<(&str, usize)> {
+    let rest = path.strip_suffix(']')?;
+    let start = rest.rfind('[')?;
+    let index = rest[start + 1..].parse().ok()?;
+    Some((&path[..start], index))
+}
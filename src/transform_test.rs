@@ -0,0 +1,155 @@
+#[cfg(test)]
+mod tests {
+    use crate::delta::Delta;
+    use crate::delta::Operation::{Add, Change, Delete};
+    use crate::differ::Differ;
+    use crate::patcher::{patch, PatchOptions};
+    use crate::transform::transform;
+    use serde_json::{json, Value, Value::Null};
+
+    fn diff(left: &Value, right: &Value) -> Vec<Delta> {
+        let mut differ = Differ::new_from_json_values(left.clone(), right.clone());
+        differ.diff().get_deltas().clone()
+    }
+
+    /// Applies both orders, checks they give `expected` and returns the paths
+    /// of the conflicts
+    fn assert_converges(base: Value, ours: Value, theirs: Value, expected: Value) -> Vec<String> {
+        let (a, b) = (diff(&base, &ours), diff(&base, &theirs));
+        let transformed = transform(&a, &b);
+
+        let options = PatchOptions::default();
+        let a_first = patch(patch(base.clone(), &a, options), &transformed.b, options);
+        let b_first = patch(patch(base, &b, options), &transformed.a, options);
+
+        assert_eq!(a_first, expected);
+        assert_eq!(b_first, expected);
+
+        transformed
+            .conflicts
+            .into_iter()
+            .map(|conflict| conflict.path)
+            .collect()
+    }
+
+    #[test]
+    fn transform_concurrent_appends() {
+        let conflicts = assert_converges(
+            json!({"list": [1, 2]}),
+            json!({"list": [1, 2, 3]}),
+            json!({"list": [1, 2, {"x": 4}, 5]}),
+            json!({"list": [1, 2, 3, {"x": 4}, 5]}),
+        );
+        assert!(conflicts.is_empty());
+    }
+
+    #[test]
+    fn transform_append_and_removal() {
+        assert_converges(
+            json!({"list": [1, 2, 3]}),
+            json!({"list": [1, 2, 3, 4]}),
+            json!({"list": [1]}),
+            json!({"list": [1, 4]}),
+        );
+        assert_converges(
+            json!({"list": [1, 2, 3]}),
+            json!({"list": [1, 2]}),
+            json!({"list": [1]}),
+            json!({"list": [1]}),
+        );
+    }
+
+    #[test]
+    fn transform_edit_of_removed_element() {
+        let conflicts = assert_converges(
+            json!([{"a": 1}, {"a": 2}]),
+            json!([{"a": 1}, {"a": 3}]),
+            json!([{"a": 1}]),
+            json!([{"a": 1}]),
+        );
+        assert_eq!(conflicts, ["$[1]"]);
+
+        let conflicts = assert_converges(
+            json!([{"a": 1}, {"a": 2}]),
+            json!([{"a": 1}]),
+            json!([{"a": 1}, {"a": 3}]),
+            json!([{"a": 1}]),
+        );
+        assert_eq!(conflicts, ["$[1]"]);
+    }
+
+    #[test]
+    fn transform_appends_to_empty_array() {
+        assert_converges(
+            json!({"list": []}),
+            json!({"list": [1]}),
+            json!({"list": [2, 3]}),
+            json!({"list": [1, 2, 3]}),
+        );
+    }
+
+    #[test]
+    fn transform_conflict_keeps_a() {
+        let conflicts = assert_converges(
+            json!({"a": 1, "b": {"c": 1}}),
+            json!({"a": 2, "b": 2}),
+            json!({"a": 3, "b": {"c": 3, "d": 4}}),
+            json!({"a": 2, "b": 2}),
+        );
+        assert_eq!(conflicts, ["$.a", "$.b"]);
+    }
+
+    #[test]
+    fn transform_reports_lost_edits() {
+        let base = json!([1, 2, 3]);
+        let (a, b) = (
+            diff(&base, &json!([0, 1, 2, 3])),
+            diff(&base, &json!([1, 2, 30])),
+        );
+        let transformed = transform(&a, &b);
+
+        assert!(!transformed.is_clean());
+        assert_eq!(transformed.conflicts.len(), 1);
+        let conflict = &transformed.conflicts[0];
+        assert_eq!(conflict.path, "$[2]");
+        assert_eq!(
+            conflict.a,
+            vec![Delta::new(Change, "$[2]".to_string(), json!(3), json!(2))]
+        );
+        assert_eq!(
+            conflict.b,
+            vec![Delta::new(Change, "$[2]".to_string(), json!(3), json!(30))]
+        );
+        assert!(transformed.b.is_empty());
+    }
+
+    #[test]
+    fn transform_shifts_indices() {
+        let a = vec![Delta::new(Add, "$.l[2]".to_string(), Null, json!("a"))];
+        let b = vec![
+            Delta::new(Change, "$.l[0]".to_string(), json!(0), json!(9)),
+            Delta::new(Add, "$.l[2]".to_string(), Null, json!("b")),
+        ];
+
+        let transformed = transform(&a, &b);
+
+        assert!(transformed.is_clean());
+        assert_eq!(transformed.a, a);
+        assert_eq!(
+            transformed.b,
+            vec![
+                Delta::new(Change, "$.l[0]".to_string(), json!(0), json!(9)),
+                Delta::new(Add, "$.l[3]".to_string(), Null, json!("b")),
+            ]
+        );
+
+        let b = vec![Delta::new(Delete, "$.l[1]".to_string(), json!(1), Null)];
+        let transformed = transform(&a, &b);
+
+        assert_eq!(
+            transformed.a,
+            vec![Delta::new(Add, "$.l[1]".to_string(), Null, json!("a"))]
+        );
+        assert_eq!(transformed.b, b);
+    }
+}
@@ -1,7 +1,8 @@
 use crate::patcher::PatchOptions;
-use regex::Regex;
 use serde_json::{json, Value};
+use std::borrow::Cow;
 use std::collections::hash_map::DefaultHasher;
+use std::fmt::Write;
 
 use std::hash::{Hash, Hasher};
 
@@ -11,107 +12,226 @@ pub fn calculate_hash<T: Hash>(t: &T) -> u64 {
     s.finish()
 }
 
-pub fn is_multi_dimensional_array(json_path: &str) -> (bool, usize, Vec<usize>) {
-    if !json_path.contains('[') {
-        return (false, 0, Vec::new());
+/// Appends an object key to a delta path. Keys that are empty or hold `.`,
+/// `[`, `]` or `'` are written as `['key']`, with `\` and `'` escaped.
+pub fn push_key(path: &mut String, key: &str) {
+    if !key.is_empty() && !key.contains(['.', '[', ']', '\'']) {
+        path.push('.');
+        path.push_str(key);
+        return;
     }
 
-    let array_index_regex = Regex::new(r"\[(\d+)]").unwrap();
+    path.push_str("['");
+    for character in key.chars() {
+        if matches!(character, '\\' | '\'') {
+            path.push('\\');
+        }
+        path.push(character);
+    }
+    path.push_str("']");
+}
 
-    let indices: Vec<usize> = array_index_regex
-        .captures_iter(json_path)
-        .filter_map(|capture| capture.get(1).and_then(|m| m.as_str().parse().ok()))
-        .collect();
+/// Appends an array index to a delta path
+pub fn push_index(path: &mut String, index: usize) {
+    let _ = write!(path, "[{}]", index);
+}
 
-    let indices_len = indices.len();
-    let is_multi_dimensional = indices_len > 0;
+/// A segment of a delta path such as `$.a[0]`
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub enum Segment<'a> {
+    Index(usize),
+    Key(Cow<'a, str>),
+}
 
-    (is_multi_dimensional, indices_len - 1, indices)
+/// Iterator over the segments of a delta path, see `segments`
+pub struct Segments<'a> {
+    rest: &'a str,
 }
 
-#[derive(Debug)]
-pub struct PathItem {
-    pub key: String,
-    pub is_array: bool,
-    pub indices: Vec<usize>,
-    pub is_last: bool,
+/// Splits a delta path into its keys and array indices, the leading `$` is
+/// skipped. Stops at the first malformed segment, see `parse_path`.
+pub fn segments(path: &str) -> Segments<'_> {
+    Segments {
+        rest: path.strip_prefix('$').unwrap_or(path),
+    }
 }
 
-impl PathItem {
-    pub fn new(path: String, is_array: bool, indices: Vec<usize>, is_last: bool) -> Self {
-        PathItem {
-            key: get_key(path.as_str()),
-            is_array,
-            indices,
-            is_last,
+/// Splits a delta path into its keys and array indices, or returns why it is
+/// malformed
+pub fn parse_path(path: &str) -> Result<Vec<Segment<'_>>, &'static str> {
+    let mut rest = path.strip_prefix('$').ok_or("it must start with $")?;
+    let mut path_segments = Vec::new();
+
+    while let Some((segment, next)) = next_segment(rest)? {
+        path_segments.push(segment);
+        rest = next;
+    }
+
+    Ok(path_segments)
+}
+
+impl<'a> Iterator for Segments<'a> {
+    type Item = Segment<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match next_segment(self.rest) {
+            Ok(Some((segment, rest))) => {
+                self.rest = rest;
+                Some(segment)
+            }
+            _ => {
+                self.rest = "";
+                None
+            }
         }
     }
 }
 
-pub fn analyse_path(json_path: &str) -> Vec<PathItem> {
-    let split_path = json_path.split('.');
-    let mut path_items: Vec<PathItem> = vec![];
-    let total_path_count = split_path.clone().count();
+/// Reads the segment at the start of `text`, returns it with the rest of the
+/// path, or `None` at the end of the path
+fn next_segment(text: &str) -> Result<Option<(Segment<'_>, &str)>, &'static str> {
+    if text.is_empty() {
+        return Ok(None);
+    }
 
-    for (index, path) in split_path.into_iter().enumerate() {
-        let (is_array, _, indices) = is_multi_dimensional_array(path);
-        let is_last = index == total_path_count - 1;
+    if let Some(rest) = text.strip_prefix("['") {
+        let (key, rest) = quoted_key(rest)?;
+        return Ok(Some((Segment::Key(key), rest)));
+    }
 
-        let path_item = PathItem::new(path.to_string(), is_array, indices, is_last);
+    if let Some(rest) = text.strip_prefix('[') {
+        let end = rest.find(']').ok_or("a bracket is not closed")?;
+        let index = &rest[..end];
+        if index.is_empty() || !index.bytes().all(|byte| byte.is_ascii_digit()) {
+            return Err("an index is not a number");
+        }
+        let index = index.parse().map_err(|_| "an index is too large")?;
+        return Ok(Some((Segment::Index(index), &rest[end + 1..])));
+    }
 
-        path_items.push(path_item);
+    let rest = text.strip_prefix('.').ok_or("a key must follow a dot")?;
+    let end = rest.find(['.', '[']).unwrap_or(rest.len());
+    if end == 0 {
+        return Err("a key is empty");
+    }
+    Ok(Some((
+        Segment::Key(Cow::Borrowed(&rest[..end])),
+        &rest[end..],
+    )))
+}
+
+/// Reads a key written by `push_key` as `['key']`, from after the opening
+/// quote. Returns the key and what follows the closing `']`.
+fn quoted_key(text: &str) -> Result<(Cow<'_, str>, &str), &'static str> {
+    let mut key = String::new();
+    let mut escaped = false;
+
+    for (position, character) in text.char_indices() {
+        match character {
+            _ if escaped => {
+                key.push(character);
+                escaped = false;
+            }
+            '\\' => escaped = true,
+            '\'' => {
+                let rest = text[position + 1..]
+                    .strip_prefix(']')
+                    .ok_or("a bracket is not closed")?;
+                if key.len() == position {
+                    return Ok((Cow::Borrowed(&text[..position]), rest));
+                }
+                return Ok((Cow::Owned(key), rest));
+            }
+            _ => key.push(character),
+        }
     }
 
-    path_items
+    Err("a quote is not closed")
 }
 
-pub fn insert_element(array: &mut Vec<Value>, indices: &[usize], value: Value) {
-    if indices.is_empty() {
-        return;
+/// Returns true if `prefix` is `path` itself or one of its ancestors, such as
+/// `$.a` for `$.a[0].b`
+pub fn is_path_prefix(prefix: &str, path: &str) -> bool {
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with(['.', '[']),
+        None => false,
     }
+}
 
-    let index = indices.first().unwrap();
+/// Returns the ancestors of a delta path, from the root `$` down, followed by
+/// the path itself
+pub fn path_prefixes(path: &str) -> impl Iterator<Item = &str> {
+    let mut segments = segments(path);
+    std::iter::from_fn(move || {
+        let start = path.len() - segments.rest.len();
+        segments.next().map(|_| &path[..start])
+    })
+    .filter(|prefix| !prefix.is_empty())
+    .chain(std::iter::once(path))
+}
+
+/// Returns the value at the given path segments, if any
+pub fn get<'v, 's>(
+    value: &'v Value,
+    path: impl IntoIterator<Item = Segment<'s>>,
+) -> Option<&'v Value> {
+    path.into_iter()
+        .try_fold(value, |current, segment| match (segment, current) {
+            (Segment::Key(key), Value::Object(object)) => object.get(key.as_ref()),
+            (Segment::Index(index), Value::Array(array)) => array.get(index),
+            _ => None,
+        })
+}
 
-    if indices.len() == 1 {
+/// Inserts the value at the given indices, returns `None` if an index is past
+/// the end of its array
+pub fn insert_element(array: &mut Vec<Value>, indices: &[usize], value: Value) -> Option<()> {
+    let (index, rest) = indices.split_first()?;
+
+    if *index > array.len() {
+        return None;
+    }
+
+    if rest.is_empty() {
         array.insert(*index, value);
-        return;
+        return Some(());
     }
 
-    if *index >= array.len() {
+    if *index == array.len() {
         array.push(json!([]));
     }
 
     let element = &mut array[*index];
 
     if let Value::Array(arr) = element {
-        return insert_element(arr, &indices[1..], value);
+        return insert_element(arr, rest, value);
     }
 
     *element = value;
+    Some(())
 }
 
-pub fn change_element(array: &mut Vec<Value>, indices: &[usize], value: Value) {
-    if indices.is_empty() {
-        return;
-    }
-
-    let index = indices.first().unwrap();
+/// Replaces the value at the given indices, returns `None` if an index is past
+/// the end of its array
+pub fn change_element(array: &mut Vec<Value>, indices: &[usize], value: Value) -> Option<()> {
+    let (index, rest) = indices.split_first()?;
 
     if *index >= array.len() {
-        if indices.len() == 1 {
-            array.insert(*index, value);
-            return;
+        if rest.is_empty() && *index == array.len() {
+            array.push(value);
+            return Some(());
         }
-        return;
+        return None;
     }
 
     let element = &mut array[*index];
 
-    if let Value::Array(arr) = element {
-        return change_element(arr, &indices[1..], value);
+    if let (Value::Array(arr), false) = (&mut *element, rest.is_empty()) {
+        return change_element(arr, rest, value);
     }
 
     *element = value;
+    Some(())
 }
 
 pub fn remove_element(array: &mut Vec<Value>, indices: &[usize], options: PatchOptions) {
@@ -127,7 +247,7 @@ pub fn remove_element(array: &mut Vec<Value>, indices: &[usize], options: PatchO
 
     let element = &mut array[*index];
 
-    if let Value::Array(arr) = element {
+    if let (Value::Array(arr), true) = (&mut *element, indices.len() > 1) {
         // If we are removing the last element of an array, and the array is the only element of the parent array,
         if indices.len() == 2 && arr.len() == 1 && options.omit_empty {
             array.remove(*index);
@@ -140,15 +260,6 @@ pub fn remove_element(array: &mut Vec<Value>, indices: &[usize], options: PatchO
     array.remove(*index);
 }
 
-fn get_key(key: &str) -> String {
-    if !key.contains('[') {
-        return key.to_string();
-    }
-
-    let start = key.find('[');
-    key[..start.unwrap()].to_string()
-}
-
 pub fn remove_empty_levels(json: &mut Value) {
     match json {
         Value::Object(obj) => {
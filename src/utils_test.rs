@@ -1,2 +1,56 @@
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use crate::utils::{path_prefixes, push_index, push_key, segments, Segment};
+
+    #[test]
+    fn test_push_key_escapes_path_syntax() {
+        let cases = [
+            ("a", "$.a"),
+            ("", "$['']"),
+            ("a.b", "$['a.b']"),
+            ("a[0]", "$['a[0]']"),
+            ("it's", r"$['it\'s']"),
+            (r"a\'.", r"$['a\\\'.']"),
+        ];
+
+        for (key, expected) in cases {
+            let mut path = "$".to_string();
+            push_key(&mut path, key);
+            assert_eq!(path, expected);
+            assert_eq!(
+                segments(&path).collect::<Vec<_>>(),
+                [Segment::Key(key.into())]
+            );
+        }
+    }
+
+    #[test]
+    fn test_segments_round_trip() {
+        let mut path = "$".to_string();
+        push_key(&mut path, "a.b");
+        push_index(&mut path, 2);
+        push_key(&mut path, "");
+        push_key(&mut path, "c");
+        assert_eq!(path, "$['a.b'][2][''].c");
+
+        assert_eq!(
+            segments(&path).collect::<Vec<_>>(),
+            [
+                Segment::Key("a.b".into()),
+                Segment::Index(2),
+                Segment::Key("".into()),
+                Segment::Key("c".into()),
+            ]
+        );
+        assert_eq!(
+            path_prefixes(&path).collect::<Vec<_>>(),
+            [
+                "$",
+                "$['a.b']",
+                "$['a.b'][2]",
+                "$['a.b'][2]['']",
+                path.as_str()
+            ]
+        );
+    }
+}
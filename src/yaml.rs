@@ -0,0 +1,211 @@
+//! YAML documents, behind the `yaml` feature.
+//!
+//! Documents are converted to and from `Value`, so deltas use the same paths
+//! as for JSON. Mapping keys that are numbers or booleans become strings.
+//! A stream of several documents separated by `---` reads as an array with
+//! one element per document. `patch` keeps the key order and the tags, such
+//! as `!Ref`, of the original document, but not its comments, which the YAML
+//! parser drops.
+
+use crate::delta::Delta;
+use crate::errors::ProcessError;
+use crate::patcher::{try_patch, PatchOptions};
+use serde::Deserialize;
+use serde_json::{Map, Number, Value};
+use serde_yaml::value::TaggedValue;
+use serde_yaml::{Mapping, Value as Yaml};
+use std::mem::discriminant;
+
+/// Parses a YAML document into a `Value`, or a stream of documents into an
+/// array of them
+pub fn from_str(text: &str) -> Result<Value, ProcessError> {
+    documents_to_json(parse(text)?)
+}
+
+/// Writes a `Value` as a YAML document
+pub fn to_string(value: &Value) -> Result<String, ProcessError> {
+    write(&to_yaml(value, None))
+}
+
+/// Applies deltas to a YAML document, or stream of documents, and writes it
+/// back. Keys keep their order and new keys come after the existing ones.
+/// Tags are kept on the values that keep their type.
+pub fn patch(text: &str, deltas: &[Delta]) -> Result<String, ProcessError> {
+    let original = parse(text)?;
+    let patched = try_patch(
+        documents_to_json(original.clone())?,
+        deltas,
+        PatchOptions::default(),
+    )?;
+
+    match (&original[..], &patched) {
+        ([document], _) => write(&to_yaml(&patched, Some(document))),
+        (_, Value::Array(documents)) => {
+            let documents = documents
+                .iter()
+                .enumerate()
+                .map(|(index, document)| write(&to_yaml(document, original.get(index))))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(documents.join("---\n"))
+        }
+        _ => write(&to_yaml(&patched, None)),
+    }
+}
+
+/// Parses the documents of a YAML stream, an empty text holds one null
+/// document
+fn parse(text: &str) -> Result<Vec<Yaml>, ProcessError> {
+    let documents = serde_yaml::Deserializer::from_str(text)
+        .map(|document| Yaml::deserialize(document).map_err(|error| invalid(error.to_string())))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if documents.is_empty() {
+        return Ok(vec![Yaml::Null]);
+    }
+    Ok(documents)
+}
+
+fn documents_to_json(mut documents: Vec<Yaml>) -> Result<Value, ProcessError> {
+    if documents.len() == 1 {
+        return to_json(documents.remove(0));
+    }
+
+    documents
+        .into_iter()
+        .map(to_json)
+        .collect::<Result<_, _>>()
+        .map(Value::Array)
+}
+
+fn write(value: &Yaml) -> Result<String, ProcessError> {
+    serde_yaml::to_string(value).map_err(|error| invalid(error.to_string()))
+}
+
+fn to_json(value: Yaml) -> Result<Value, ProcessError> {
+    let value = match value {
+        Yaml::Null => Value::Null,
+        Yaml::Bool(value) => Value::Bool(value),
+        Yaml::Number(number) => {
+            if let Some(number) = number.as_i64() {
+                Value::from(number)
+            } else if let Some(number) = number.as_u64() {
+                Value::from(number)
+            } else {
+                number
+                    .as_f64()
+                    .and_then(Number::from_f64)
+                    .map(Value::Number)
+                    .ok_or_else(|| invalid(format!("{} is not a JSON number", number)))?
+            }
+        }
+        Yaml::String(value) => Value::String(value),
+        Yaml::Sequence(sequence) => Value::Array(
+            sequence
+                .into_iter()
+                .map(to_json)
+                .collect::<Result<_, _>>()?,
+        ),
+        Yaml::Mapping(mapping) => {
+            let mut object = Map::new();
+            for (key, value) in mapping {
+                let key = key_string(&key)
+                    .ok_or_else(|| invalid(format!("{:?} is not a valid key", key)))?;
+                object.insert(key, to_json(value)?);
+            }
+            Value::Object(object)
+        }
+        Yaml::Tagged(tagged) => to_json(tagged.value)?,
+    };
+
+    Ok(value)
+}
+
+/// Converts a value to YAML, following the key order, key types and tags of
+/// `template` where it has the same keys
+fn to_yaml(value: &Value, template: Option<&Yaml>) -> Yaml {
+    let Some(Yaml::Tagged(tagged)) = template else {
+        return untagged_to_yaml(value, template);
+    };
+
+    // the tag is dropped when the value changes type, such as a string
+    // replaced by a number
+    let converted = untagged_to_yaml(value, Some(&tagged.value));
+    if discriminant(&converted) != discriminant(&tagged.value) {
+        return converted;
+    }
+
+    Yaml::Tagged(Box::new(TaggedValue {
+        tag: tagged.tag.clone(),
+        value: converted,
+    }))
+}
+
+fn untagged_to_yaml(value: &Value, template: Option<&Yaml>) -> Yaml {
+    match value {
+        Value::Null => Yaml::Null,
+        Value::Bool(value) => Yaml::Bool(*value),
+        Value::Number(number) => {
+            if let Some(number) = number.as_i64() {
+                Yaml::from(number)
+            } else if let Some(number) = number.as_u64() {
+                Yaml::from(number)
+            } else {
+                Yaml::from(number.as_f64().unwrap_or_default())
+            }
+        }
+        Value::String(value) => Yaml::String(value.clone()),
+        Value::Array(array) => {
+            let elements = match template {
+                Some(Yaml::Sequence(sequence)) => sequence.as_slice(),
+                _ => &[],
+            };
+            Yaml::Sequence(
+                array
+                    .iter()
+                    .enumerate()
+                    .map(|(index, value)| to_yaml(value, elements.get(index)))
+                    .collect(),
+            )
+        }
+        Value::Object(object) => {
+            let mut mapping = Mapping::new();
+            if let Some(Yaml::Mapping(original)) = template {
+                for (key, original) in original {
+                    if let Some(value) = key_string(key).and_then(|key| object.get(&key)) {
+                        mapping.insert(key.clone(), to_yaml(value, Some(original)));
+                    }
+                }
+            }
+
+            for (key, value) in object {
+                if !contains_key(&mapping, key) {
+                    mapping.insert(Yaml::String(key.clone()), to_yaml(value, None));
+                }
+            }
+            Yaml::Mapping(mapping)
+        }
+    }
+}
+
+/// Returns true if the mapping has a key that reads as `key`
+fn contains_key(mapping: &Mapping, key: &str) -> bool {
+    mapping
+        .keys()
+        .any(|other| key_string(other).as_deref() == Some(key))
+}
+
+fn key_string(key: &Yaml) -> Option<String> {
+    match key {
+        Yaml::String(key) => Some(key.clone()),
+        Yaml::Number(number) => Some(number.to_string()),
+        Yaml::Bool(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+fn invalid(message: String) -> ProcessError {
+    ProcessError::InvalidDocument {
+        format: "YAML".to_string(),
+        message,
+    }
+}
@@ -0,0 +1,95 @@
+#[cfg(test)]
+mod tests {
+    use crate::differ::Differ;
+    use crate::yaml;
+    use serde_json::json;
+
+    const MANIFEST: &str = "\
+kind: Deployment
+metadata:
+  name: web
+  labels:
+    app: web
+spec:
+  replicas: 2
+  ports:
+  - 80
+  - 443
+";
+
+    #[test]
+    fn yaml_from_str() {
+        assert_eq!(
+            yaml::from_str("b: 1\na: [true, ~, x]\n1: 2.5").unwrap(),
+            json!({"b": 1, "a": [true, null, "x"], "1": 2.5})
+        );
+        assert!(yaml::from_str("a: [").is_err());
+        assert!(yaml::from_str("[1]: x").is_err());
+    }
+
+    #[test]
+    fn yaml_diff_and_patch() {
+        let changed = MANIFEST.replace("replicas: 2", "replicas: 3");
+        let changed = changed.replace("  - 443\n", "  - 443\n  - 8080\n");
+
+        let mut differ = Differ::new_from_yaml(MANIFEST, &changed).unwrap();
+        let deltas = differ.diff().get_deltas().clone();
+        assert_eq!(deltas.len(), 2);
+        assert_eq!(deltas[0].path, "$.spec.ports[2]");
+        assert_eq!(deltas[1].path, "$.spec.replicas");
+
+        // keys keep the order of the original document
+        assert_eq!(yaml::patch(MANIFEST, &deltas).unwrap(), changed);
+    }
+
+    #[test]
+    fn yaml_patch_appends_new_keys() {
+        let deltas = Differ::new_from_json_values(
+            json!({"z": 1, "a": 1}),
+            json!({"z": 1, "a": 1, "m": {"k": 2}}),
+        )
+        .diff()
+        .get_deltas()
+        .clone();
+
+        assert_eq!(
+            yaml::patch("z: 1\na: 1\n", &deltas).unwrap(),
+            "z: 1\na: 1\nm:\n  k: 2\n"
+        );
+        assert_eq!(yaml::to_string(&json!({"a": [1]})).unwrap(), "a:\n- 1\n");
+    }
+
+    #[test]
+    fn yaml_patch_keeps_tags() {
+        let template = "\
+Bucket: !Ref BucketName
+Name: !Sub '${AWS::StackName}-web'
+Size: 1
+";
+        let mut right = yaml::from_str(template).unwrap();
+        right["Size"] = json!(2);
+        right["Name"] = json!("${AWS::StackName}-api");
+
+        let mut differ = Differ::new_from_json_values(yaml::from_str(template).unwrap(), right);
+        assert_eq!(
+            yaml::patch(template, differ.diff().get_deltas()).unwrap(),
+            "Bucket: !Ref BucketName\nName: !Sub ${AWS::StackName}-api\nSize: 2\n"
+        );
+    }
+
+    #[test]
+    fn yaml_multiple_documents() {
+        let stream = "a: 1\n---\nb: [x]\n";
+        assert_eq!(
+            yaml::from_str(stream).unwrap(),
+            json!([{"a": 1}, {"b": ["x"]}])
+        );
+
+        let changed = "a: 1\n---\nb: [x, y]\n";
+        let mut differ = Differ::new_from_yaml(stream, changed).unwrap();
+        assert_eq!(
+            yaml::patch(stream, differ.diff().get_deltas()).unwrap(),
+            "a: 1\n---\nb:\n- x\n- y\n"
+        );
+    }
+}